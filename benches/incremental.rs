@@ -0,0 +1,100 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Compares reparsing a multi-megabyte document from scratch against reparsing it incrementally
+//! after small edits. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use lithium::ldn::incremental::Document;
+use lithium::ldn::{Parser, Span};
+
+const RECORDS: usize = 40_000;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let text = build_document();
+    let lines = text.lines().count();
+
+    println!(
+        "document: {:.1} MiB, {} lines",
+        text.len() as f64 / (1024.0 * 1024.0),
+        lines
+    );
+
+    let full = measure(|_| {
+        Parser::from(text.as_ref()).parse_recovering();
+    });
+
+    println!("full reparse:                 {:>10.3} ms", as_ms(full));
+
+    for &(name, record) in &[("start", 0), ("middle", RECORDS / 2), ("end", RECORDS - 1)] {
+        // Points at the first value of the record's `:values` list.
+        let line = record * 6 + 4;
+
+        let mut document = Document::new(text.clone());
+
+        let incremental = measure(|i| {
+            // Alternate between inserting and removing a digit, so that the document stays valid.
+            if i % 2 == 0 {
+                document.edit(&Span::from_parts(line, 17, line, 17), "1");
+            } else {
+                document.edit(&Span::from_parts(line, 17, line, 18), "");
+            }
+        });
+
+        println!(
+            "incremental edit ({:<6}):     {:>10.3} ms ({:.0}x faster)",
+            name,
+            as_ms(incremental),
+            full.as_secs_f64() / incremental.as_secs_f64()
+        );
+    }
+}
+
+/// Builds a log-like document with many top-level records.
+fn build_document() -> String {
+    let mut text = String::new();
+
+    for i in 0..RECORDS {
+        text += &format!(
+            concat!(
+                "; record {}\n",
+                "(record :id {}\n",
+                "        :name \"entry number {}\"\n",
+                "        :tags (alpha beta gamma)\n",
+                "        :values (1 2 3 4 5 6 7 8 9 10))\n\n"
+            ),
+            i, i, i
+        );
+    }
+
+    text
+}
+
+/// Returns the average time taken by `f` over a fixed number of iterations.
+fn measure<F: FnMut(u32)>(mut f: F) -> Duration {
+    let start = Instant::now();
+
+    for i in 0..ITERATIONS {
+        f(i);
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
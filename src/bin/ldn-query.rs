@@ -0,0 +1,78 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+use lithium::ldn::{Options, Parser, Query};
+
+const USAGE: &str = "usage: ldn-query [--spans] QUERY [FILE]";
+
+/// Prints the items selected by a query out of a file, or out of the standard input, one per line.
+fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    let spans = args.first().map(String::as_str) == Some("--spans");
+
+    if spans {
+        args.remove(0);
+    }
+
+    let (query, path) = match args.as_slice() {
+        [query] => (query, None),
+        [query, path] => (query, Some(path)),
+        _ => fail(USAGE),
+    };
+
+    let query = query
+        .parse::<Query>()
+        .unwrap_or_else(|err| fail(&format!("query: {}", err)));
+
+    let text = match path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+    }
+    .unwrap_or_else(|err| fail(&err.to_string()));
+
+    let items = Parser::from(text.as_str())
+        .with_options(Options::extended())
+        .parse()
+        .unwrap_or_else(|err| fail(&err.to_string()));
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    for item in query.select(&items) {
+        let result = if spans {
+            writeln!(stdout, "{} {}", item.span(), item)
+        } else {
+            writeln!(stdout, "{}", item)
+        };
+
+        // Stop quietly when the output is closed early, for instance by `head`.
+        if result.is_err() {
+            break;
+        }
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
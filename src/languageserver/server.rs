@@ -22,7 +22,9 @@ use languageserver_types::*;
 use serde_json::json;
 
 use super::super::ldn;
-use super::super::ldn::{fmt, List, Parser};
+use super::super::ldn::fmt;
+use super::super::ldn::incremental::Document;
+use super::super::ldn::parser::is_newline;
 use super::handler::LanguageServerHandler;
 use super::stdio_transport::{read_request, write_response};
 
@@ -46,7 +48,7 @@ pub fn run() -> io::Result<()> {
 /// Builds the language server handle.
 fn build_handler() -> LanguageServerHandler {
     let mut handler = LanguageServerHandler::default();
-    let workspace: Arc<Mutex<HashMap<url::Url, List>>> = Arc::new(Mutex::new(HashMap::new()));
+    let workspace: Arc<Mutex<HashMap<url::Url, Document>>> = Arc::new(Mutex::new(HashMap::new()));
 
     // initialize
     handler.add_method::<request::Initialize, _>(|_params| {
@@ -56,7 +58,7 @@ fn build_handler() -> LanguageServerHandler {
 
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
                     TextDocumentSyncOptions {
-                        change: Some(TextDocumentSyncKind::Full),
+                        change: Some(TextDocumentSyncKind::Incremental),
                         open_close: Some(true),
                         ..TextDocumentSyncOptions::default()
                     },
@@ -74,16 +76,12 @@ fn build_handler() -> LanguageServerHandler {
         handler.add_method::<request::Formatting, _>(move |params| {
             let workspace = workspace.lock().unwrap();
 
-            match workspace.get(&params.text_document.uri) {
-                None => None,
-                Some(ast) => Some(vec![TextEdit::new(
-                    Range::new(
-                        Position::new(0, 0),
-                        Position::new(u64::max_value(), u64::max_value()),
-                    ),
-                    fmt(ast),
-                )]),
-            }
+            workspace.get(&params.text_document.uri).map(|document| {
+                vec![TextEdit::new(
+                    Range::new(Position::new(0, 0), Position::new(u64::MAX, u64::MAX)),
+                    fmt(&document.items()),
+                )]
+            })
         });
     }
 
@@ -94,18 +92,18 @@ fn build_handler() -> LanguageServerHandler {
         handler.add_notification::<notification::DidOpenTextDocument, _>(move |params| {
             let mut workspace = workspace.lock().unwrap();
 
-            match Parser::from(params.text_document.text.as_ref()).parse() {
-                Err(err) => {
-                    workspace.remove(&params.text_document.uri);
-                    write_diagnostic(params.text_document.uri, Some(err))
-                        .expect("sent diagnostic on file open");
-                }
-                Ok(ast) => {
-                    workspace.insert(params.text_document.uri.clone(), ast);
-                    write_diagnostic(params.text_document.uri, None)
-                        .expect("clear diagnostic on file open");
-                }
-            }
+            // Keep the document around even when broken, so that formatting keeps working.
+            let document =
+                Document::with_options(params.text_document.text, ldn::Options::extended());
+
+            write_diagnostic(
+                params.text_document.uri.clone(),
+                document.text(),
+                document.errors(),
+            )
+            .expect("sent diagnostic on file open");
+
+            workspace.insert(params.text_document.uri, document);
         });
     }
 
@@ -116,18 +114,31 @@ fn build_handler() -> LanguageServerHandler {
         handler.add_notification::<notification::DidChangeTextDocument, _>(move |params| {
             let mut workspace = workspace.lock().unwrap();
 
-            match Parser::from(params.content_changes[0].text.as_ref()).parse() {
-                Err(err) => {
-                    workspace.remove(&params.text_document.uri);
-                    write_diagnostic(params.text_document.uri, Some(err))
-                        .expect("sent diagnostic on file change");
-                }
-                Ok(ast) => {
-                    workspace.insert(params.text_document.uri.clone(), ast);
-                    write_diagnostic(params.text_document.uri, None)
-                        .expect("clear diagnostic on file change");
+            let uri = params.text_document.uri;
+
+            for change in params.content_changes {
+                match change.range {
+                    // Full document sync.
+                    None => {
+                        workspace.insert(
+                            uri.clone(),
+                            Document::with_options(change.text, ldn::Options::extended()),
+                        );
+                    }
+                    Some(range) => {
+                        if let Some(document) = workspace.get_mut(&uri) {
+                            let span = to_span(document.text(), &range);
+
+                            document.edit(&span, &change.text);
+                        }
+                    }
                 }
             }
+
+            if let Some(document) = workspace.get(&uri) {
+                write_diagnostic(uri.clone(), document.text(), document.errors())
+                    .expect("sent diagnostic on file change");
+            }
         });
     }
 
@@ -139,7 +150,7 @@ fn build_handler() -> LanguageServerHandler {
             let mut workspace = workspace.lock().unwrap();
 
             workspace.remove(&params.text_document.uri);
-            write_diagnostic(params.text_document.uri, None)
+            write_diagnostic(params.text_document.uri, "", vec![])
                 .expect("clear diagnostic on file close");
         });
     }
@@ -150,8 +161,13 @@ fn build_handler() -> LanguageServerHandler {
     handler
 }
 
-/// Writes the given diagnostic to the given writer.
-pub fn write_diagnostic(document_uri: url::Url, err: Option<ldn::Error>) -> io::Result<()> {
+/// Publishes the given errors, found in `text`, as diagnostics for a document. An empty list of
+/// errors clears any diagnostic previously published for the document.
+pub fn write_diagnostic(
+    document_uri: url::Url,
+    text: &str,
+    errors: Vec<ldn::Error>,
+) -> io::Result<()> {
     // TODO(lvillani): I'm not sure this is the right way to construct a server-to-client
     // notification.
     let envelope = jsonrpc_core::Notification {
@@ -160,10 +176,10 @@ pub fn write_diagnostic(document_uri: url::Url, err: Option<ldn::Error>) -> io::
         params: jsonrpc_core::Params::Map(
             json!(PublishDiagnosticsParams {
                 uri: document_uri,
-                diagnostics: match err {
-                    None => vec![],
-                    Some(err) => vec![err.into()],
-                }
+                diagnostics: errors
+                    .into_iter()
+                    .map(|err| to_diagnostic(text, err))
+                    .collect(),
             })
             .as_object()
             .expect("valid lsp message")
@@ -178,63 +194,265 @@ pub fn write_diagnostic(document_uri: url::Url, err: Option<ldn::Error>) -> io::
 }
 
 // FIXME(lvillani): I don't really like how repetitive all of this is. Can we make it shorter?
-impl Into<Diagnostic> for ldn::Error {
-    fn into(self) -> Diagnostic {
-        match self {
-            ldn::Error::IntegerLeadingZero(_, span) => Diagnostic {
-                message: "Found leading zero while parsing integer constant".into(),
-                range: span.into(),
-                severity: Some(DiagnosticSeverity::Error),
-                ..Diagnostic::default()
-            },
-            ldn::Error::IntegerParseError(_, span) => Diagnostic {
-                message: "Invalid integer constant".into(),
-                range: span.into(),
-                severity: Some(DiagnosticSeverity::Error),
-                ..Diagnostic::default()
-            },
-            ldn::Error::InvalidCharacter(_, pos) => Diagnostic {
-                message: "Invalid character".into(),
-                range: pos.into(),
-                severity: Some(DiagnosticSeverity::Error),
-                ..Diagnostic::default()
-            },
-            ldn::Error::SymbolParseError(_, span) => Diagnostic {
-                message: "Symbol parse error".into(),
-                range: span.into(),
-                severity: Some(DiagnosticSeverity::Error),
-                ..Diagnostic::default()
-            },
-            ldn::Error::UnbalancedParentheses(pos) => Diagnostic {
-                message: "Unbalanced parentheses".into(),
-                range: pos.into(),
-                severity: Some(DiagnosticSeverity::Error),
-                ..Diagnostic::default()
-            },
-            ldn::Error::Utf8Error(span) => Diagnostic {
-                message: "UTF-8 decode error".into(),
-                range: span.into(),
-                severity: Some(DiagnosticSeverity::Error),
-                ..Diagnostic::default()
-            },
+/// Converts an error found in `text` to a diagnostic.
+fn to_diagnostic(text: &str, err: ldn::Error) -> Diagnostic {
+    match err {
+        ldn::Error::CharParseError(_, span) => Diagnostic {
+            message: "Cannot parse character".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::DatumCommentWithoutValue(span) => Diagnostic {
+            message: "Datum comment without a value".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::DuplicateElement(span) => Diagnostic {
+            message: "Duplicate set element".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::DuplicateKey(span) => Diagnostic {
+            message: "Duplicate map key".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::FloatParseError(_, span) => Diagnostic {
+            message: "Invalid floating-point constant".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::IntegerLeadingZero(_, span) => Diagnostic {
+            message: "Found leading zero while parsing integer constant".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::IntegerMisplacedSeparator(_, span) => Diagnostic {
+            message: "Digit separators must be between digits".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::IntegerMissingDigits(_, span) => Diagnostic {
+            message: "Missing digits in integer constant".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::IntegerParseError(_, span) => Diagnostic {
+            message: "Invalid integer constant".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::InvalidCharacter(_, pos) => Diagnostic {
+            message: "Invalid character".into(),
+            range: to_range(text, &ldn::Span::new(pos.clone(), pos)),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::InvalidEscape(_, span) => Diagnostic {
+            message: "Invalid escape sequence".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::InvalidTaggedValue(tag, message, span) => Diagnostic {
+            message: format!("Invalid value for tag #{}: {}", tag, message),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::Io(_, message, pos) => Diagnostic {
+            message: format!("I/O error: {}", message),
+            range: to_range(text, &ldn::Span::new(pos.clone(), pos)),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::MapOddEntries(span) => Diagnostic {
+            message: "Map has a key without a value".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::MismatchedDelimiter(_, pos) => Diagnostic {
+            message: "Mismatched closing delimiter".into(),
+            range: to_range(text, &ldn::Span::new(pos.clone(), pos)),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::QuoteWithoutValue(span) => Diagnostic {
+            message: "Quote without a value".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::RatioParseError(_, span) => Diagnostic {
+            message: "Invalid ratio constant".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::RatioZeroDenominator(_, span) => Diagnostic {
+            message: "Ratio with zero denominator".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::SymbolParseError(_, span) => Diagnostic {
+            message: "Symbol parse error".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::TagWithoutValue(span) => Diagnostic {
+            message: "Tag without a value".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::UnbalancedParentheses(pos) => Diagnostic {
+            message: "Unbalanced parentheses".into(),
+            range: to_range(text, &ldn::Span::new(pos.clone(), pos)),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::UnterminatedBlockComment(span) => Diagnostic {
+            message: "Unterminated block comment".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::UnterminatedString(span) => Diagnostic {
+            message: "Unterminated string".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+        ldn::Error::Utf8Error(span) => Diagnostic {
+            message: "UTF-8 decode error".into(),
+            range: to_range(text, &span),
+            severity: Some(DiagnosticSeverity::Error),
+            ..Diagnostic::default()
+        },
+    }
+}
+
+/// Converts a span of `text`, whose columns count characters, to an LSP range, whose columns count
+/// UTF-16 code units.
+fn to_range(text: &str, span: &ldn::Span) -> Range {
+    Range::new(to_position(text, &span.start), to_position(text, &span.end))
+}
+
+/// Converts an LSP range of `text` to a span.
+fn to_span(text: &str, range: &Range) -> ldn::Span {
+    ldn::Span::new(
+        from_position(text, &range.start),
+        from_position(text, &range.end),
+    )
+}
+
+/// Converts a position in `text` to an LSP position.
+fn to_position(text: &str, pos: &ldn::Position) -> Position {
+    let line = line_at(text, pos.line);
+    let units = line
+        .chars()
+        .take(pos.column)
+        .map(char::len_utf16)
+        .sum::<usize>();
+
+    // Columns past the end of the line are kept as they are.
+    let extra = pos.column.saturating_sub(line.chars().count());
+
+    Position::new(pos.line as u64, (units + extra) as u64)
+}
+
+/// Converts an LSP position in `text` to a position.
+fn from_position(text: &str, pos: &Position) -> ldn::Position {
+    let mut units = 0;
+    let mut column = 0;
+
+    for ch in line_at(text, pos.line as usize).chars() {
+        if units >= pos.character as usize {
+            break;
         }
+
+        units += ch.len_utf16();
+        column += 1;
     }
+
+    // Columns past the end of the line are kept as they are.
+    let extra = (pos.character as usize).saturating_sub(units);
+
+    ldn::Position::new(pos.line as usize, column + extra)
 }
 
-impl Into<Range> for ldn::Span {
-    fn into(self) -> Range {
-        Range::new(
-            Position::new(self.start.line as u64, self.start.column as u64),
-            Position::new(self.end.line as u64, self.end.column as u64),
-        )
+/// Returns the given line of `text`, without its line break. Lines past the end of the text are
+/// empty.
+fn line_at(text: &str, line: usize) -> &str {
+    let mut rest = text;
+
+    for _ in 0..line {
+        match rest.find(is_newline) {
+            None => return "",
+            Some(index) if rest[index..].starts_with("\r\n") => rest = &rest[index + 2..],
+            Some(index) => rest = &rest[index + 1..],
+        }
     }
+
+    rest.split(is_newline).next().unwrap_or("")
 }
 
-impl Into<Range> for ldn::Position {
-    fn into(self) -> Range {
-        Range::new(
-            Position::new(self.line as u64, self.column as u64),
-            Position::new(self.line as u64, self.column as u64),
-        )
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_columns() {
+        // The emoji takes two UTF-16 code units, but is a single character.
+        let text = "a\r\n(\u{1f600} b)\nc";
+
+        assert_eq!(
+            ldn::Span::from_parts(1, 3, 2, 1),
+            to_span(text, &Range::new(Position::new(1, 4), Position::new(2, 1)))
+        );
+        assert_eq!(
+            Range::new(Position::new(1, 4), Position::new(2, 1)),
+            to_range(text, &ldn::Span::from_parts(1, 3, 2, 1))
+        );
+
+        // Past the end of the line.
+        assert_eq!(
+            ldn::Span::from_parts(1, 6, 3, 2),
+            to_span(text, &Range::new(Position::new(1, 7), Position::new(3, 2)))
+        );
+        assert_eq!(
+            Range::new(Position::new(1, 7), Position::new(3, 2)),
+            to_range(text, &ldn::Span::from_parts(1, 6, 3, 2))
+        );
+    }
+
+    #[test]
+    fn edit_after_emoji() {
+        let mut document = Document::new("(a \"\u{1f600}\" b) 01".into());
+        let range = Range::new(Position::new(0, 8), Position::new(0, 9));
+
+        document.edit(&to_span(document.text(), &range), "c");
+
+        assert_eq!("(a \"\u{1f600}\" c) 01", document.text());
+
+        let errors = document.errors();
+
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            Range::new(Position::new(0, 11), Position::new(0, 13)),
+            to_diagnostic(document.text(), errors[0].clone()).range
+        );
     }
 }
@@ -41,9 +41,8 @@ pub fn read_request<R: BufRead>(reader: &mut R) -> io::Result<String> {
 
         // FIXME(lvillani): I don't like how we are ignoring errors.
         content_length = line
-            .splitn(2, ':')
-            .nth(1)
-            .unwrap_or("0")
+            .split_once(':')
+            .map_or("0", |(_, v)| v)
             .trim()
             .parse::<usize>()
             .unwrap_or(0);
@@ -56,7 +55,7 @@ pub fn read_request<R: BufRead>(reader: &mut R) -> io::Result<String> {
     let mut buf = vec![0; content_length];
     reader.read_exact(&mut buf)?;
 
-    String::from_utf8(buf).or_else(|_| Err(io::Error::from(io::ErrorKind::InvalidData)))
+    String::from_utf8(buf).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))
 }
 
 /// Writes a response to the given writer.
@@ -13,17 +13,38 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::fmt;
+
+use super::fmt::fmt_inline;
+use super::integer::Integer;
 use super::pos::Span;
 
 /// A list of items.
 pub type List = Vec<Item>;
 
-/// An item is either an atom, a coment or a list of items.
-#[derive(Debug, PartialEq)]
+/// An item is either an atom, a comment, a list of items or, when enabled in the parser's
+/// `Options`, a vector, map, set, tagged element or quoted item. Maps are kept as a flat list
+/// alternating keys and values.
+///
+/// When parsing in recovering mode, malformed input is kept verbatim in an `Error` item so that
+/// the rest of the document can still be used.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Item {
     Atom(Atom),
+    /// A block comment, holding the text between the outermost delimiters.
+    BlockComment(String, Span),
     Comment(String, Span),
+    /// A datum comment, holding the commented item.
+    DatumComment(Box<Item>, Span),
+    Error(String, Span),
     List(List, Span),
+    Map(List, Span),
+    /// An item preceded by a quote shorthand (`'x`, `` `x ``, `,x` or `,@x`).
+    Quoted(Quote, Box<Item>, Span),
+    Set(List, Span),
+    /// A tagged element, made of the tag (without the leading hash) and the tagged value.
+    Tagged(String, Box<Item>, Span),
+    Vector(List, Span),
 }
 
 impl Item {
@@ -31,37 +52,246 @@ impl Item {
     pub fn span(&self) -> &Span {
         match self {
             Item::Atom(atom) => atom.span(),
+            Item::BlockComment(_, span) => span,
             Item::Comment(_, span) => span,
+            Item::DatumComment(_, span) => span,
+            Item::Error(_, span) => span,
             Item::List(_, span) => span,
+            Item::Map(_, span) => span,
+            Item::Quoted(_, _, span) => span,
+            Item::Set(_, span) => span,
+            Item::Tagged(_, _, span) => span,
+            Item::Vector(_, span) => span,
         }
     }
 
-    /// Returns `true` if the current item is a comment.
-    pub fn is_comment(&self) -> bool {
+    /// Returns a mutable reference to the `Span` associated with the item.
+    pub fn span_mut(&mut self) -> &mut Span {
         match self {
-            Item::Comment(_, _) => true,
+            Item::Atom(atom) => atom.span_mut(),
+            Item::BlockComment(_, span) => span,
+            Item::Comment(_, span) => span,
+            Item::DatumComment(_, span) => span,
+            Item::Error(_, span) => span,
+            Item::List(_, span) => span,
+            Item::Map(_, span) => span,
+            Item::Quoted(_, _, span) => span,
+            Item::Set(_, span) => span,
+            Item::Tagged(_, _, span) => span,
+            Item::Vector(_, span) => span,
+        }
+    }
+
+    /// Returns `true` if the current item is a line, block or datum comment.
+    pub fn is_comment(&self) -> bool {
+        matches!(
+            self,
+            Item::BlockComment(_, _) | Item::Comment(_, _) | Item::DatumComment(_, _)
+        )
+    }
+
+    /// Returns `true` if both items denote the same value, regardless of their spans and of how
+    /// integers were written. Comments are ignored, the order of map entries and set elements
+    /// doesn't matter, and errors are never equal to anything.
+    pub fn eq_value(&self, other: &Item) -> bool {
+        match (self, other) {
+            (Item::Atom(a), Item::Atom(b)) => a.eq_value(b),
+            (Item::List(a, _), Item::List(b, _)) | (Item::Vector(a, _), Item::Vector(b, _)) => {
+                let a = values(a);
+                let b = values(b);
+
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.eq_value(b))
+            }
+            (Item::Map(a, _), Item::Map(b, _)) => {
+                let a = values(a);
+                let b = values(b);
+
+                a.len() == b.len()
+                    && a.chunks(2).all(|entry| {
+                        b.chunks(2).any(|other| {
+                            entry.len() == other.len()
+                                && entry.iter().zip(other).all(|(a, b)| a.eq_value(b))
+                        })
+                    })
+            }
+            (Item::Set(a, _), Item::Set(b, _)) => {
+                let a = values(a);
+                let b = values(b);
+
+                a.len() == b.len() && a.iter().all(|a| b.iter().any(|b| a.eq_value(b)))
+            }
+            (Item::Quoted(a, a_value, _), Item::Quoted(b, b_value, _)) => {
+                a == b && a_value.eq_value(b_value)
+            }
+            (Item::Tagged(a, a_value, _), Item::Tagged(b, b_value, _)) => {
+                a == b && a_value.eq_value(b_value)
+            }
             _ => false,
         }
     }
 }
 
+impl fmt::Display for Item {
+    /// Writes the item as compact LDN, on a single line unless it contains line comments.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&fmt_inline(self))
+    }
+}
+
 /// An indivisible syntactic element. In other words, anything that is not a comment or a list.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Atom {
-    Integer(isize, Span),
+    Bool(bool, Span),
+    Char(char, Span),
+    /// A floating-point number, along with the literal it was written as unless that's the
+    /// shortest notation of its value (`2E10`, `1.50`). Only accepted when enabled in the parser's
+    /// `Options`.
+    Float(f64, Option<String>, Span),
+    /// An integer, along with the literal it was written as unless that's the plain decimal
+    /// notation of its value (`0xFF`, `1_000`).
+    Integer(Integer, Option<String>, Span),
+    /// A keyword, without the leading colon. Like symbols, keywords may be qualified by a
+    /// namespace (`:user/id`).
     Keyword(String, Span),
-    String(String, Span),
+    Nil(Span),
+    /// A ratio between two integers, as written. The denominator is always positive. Only accepted
+    /// when enabled in the parser's `Options`.
+    Ratio(Integer, Integer, Span),
+    /// A string, along with the notation it was written in.
+    String(String, StringStyle, Span),
+    /// A symbol, optionally qualified by a namespace (`http/get`). The slash alone is a valid
+    /// symbol without namespace.
     Symbol(String, Span),
 }
 
 impl Atom {
+    /// Returns `true` if both atoms denote the same value, regardless of their spans and of how
+    /// integers and strings were written. Ratios are equal when their numerators and denominators
+    /// are. Like in `Value`, floating-point numbers are equal when they have the same bits, so `NaN`
+    /// is equal to itself while `0.0` and `-0.0` are different.
+    pub fn eq_value(&self, other: &Atom) -> bool {
+        match (self, other) {
+            (Atom::Bool(a, _), Atom::Bool(b, _)) => a == b,
+            (Atom::Char(a, _), Atom::Char(b, _)) => a == b,
+            (Atom::Float(a, _, _), Atom::Float(b, _, _)) => a.to_bits() == b.to_bits(),
+            (Atom::Integer(a, _, _), Atom::Integer(b, _, _)) => a == b,
+            (Atom::Keyword(a, _), Atom::Keyword(b, _)) => a == b,
+            (Atom::Nil(_), Atom::Nil(_)) => true,
+            (Atom::Ratio(a, b, _), Atom::Ratio(c, d, _)) => a == c && b == d,
+            (Atom::String(a, _, _), Atom::String(b, _, _)) => a == b,
+            (Atom::Symbol(a, _), Atom::Symbol(b, _)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Returns the namespace of a qualified symbol or keyword, or `None` for any other atom.
+    pub fn namespace(&self) -> Option<&str> {
+        match self {
+            Atom::Keyword(v, _) | Atom::Symbol(v, _) => split_qualified(v).0,
+            _ => None,
+        }
+    }
+
+    /// Returns the name of a symbol or keyword, without its namespace, or `None` for any other
+    /// atom.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Atom::Keyword(v, _) | Atom::Symbol(v, _) => Some(split_qualified(v).1),
+            _ => None,
+        }
+    }
+
     /// Returns the `Span` associated with the atom.
     pub fn span(&self) -> &Span {
         match self {
-            Atom::Integer(_, span) => span,
+            Atom::Bool(_, span) => span,
+            Atom::Char(_, span) => span,
+            Atom::Float(_, _, span) => span,
+            Atom::Integer(_, _, span) => span,
             Atom::Keyword(_, span) => span,
-            Atom::String(_, span) => span,
+            Atom::Nil(span) => span,
+            Atom::Ratio(_, _, span) => span,
+            Atom::String(_, _, span) => span,
             Atom::Symbol(_, span) => span,
         }
     }
+
+    /// Returns a mutable reference to the `Span` associated with the atom.
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Atom::Bool(_, span) => span,
+            Atom::Char(_, span) => span,
+            Atom::Float(_, _, span) => span,
+            Atom::Integer(_, _, span) => span,
+            Atom::Keyword(_, span) => span,
+            Atom::Nil(span) => span,
+            Atom::Ratio(_, _, span) => span,
+            Atom::String(_, _, span) => span,
+            Atom::Symbol(_, span) => span,
+        }
+    }
+}
+
+/// The quote shorthands, each standing for a list made of a symbol and the quoted item.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Quote {
+    /// `'x`, standing for `(quote x)`.
+    Quote,
+    /// `` `x ``, standing for `(quasiquote x)`.
+    Quasiquote,
+    /// `,x`, standing for `(unquote x)`.
+    Unquote,
+    /// `,@x`, standing for `(unquote-splicing x)`.
+    UnquoteSplicing,
+}
+
+impl Quote {
+    /// Returns the shorthand written before the quoted item.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Quote::Quote => "'",
+            Quote::Quasiquote => "`",
+            Quote::Unquote => ",",
+            Quote::UnquoteSplicing => ",@",
+        }
+    }
+
+    /// Returns the name of the symbol the shorthand stands for.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Quote::Quote => "quote",
+            Quote::Quasiquote => "quasiquote",
+            Quote::Unquote => "unquote",
+            Quote::UnquoteSplicing => "unquote-splicing",
+        }
+    }
+}
+
+/// The notations a string can be written in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StringStyle {
+    /// `"a\nb"`, with escape sequences.
+    Plain,
+    /// `#"a\b"#`, taken verbatim up to a quotation mark followed by the given number of hashes.
+    Raw(usize),
+    /// A text block spanning multiple lines between triple quotation marks, with escape sequences
+    /// and with the indentation shared by its lines removed.
+    Block,
+}
+
+//
+// Private
+//
+
+/// Splits the name of a symbol or keyword into its namespace, if any, and its name.
+fn split_qualified(v: &str) -> (Option<&str>, &str) {
+    match v.split_once('/') {
+        Some((namespace, name)) if !namespace.is_empty() => (Some(namespace), name),
+        _ => (None, v),
+    }
+}
+
+/// Returns the items that are not comments.
+fn values(items: &[Item]) -> Vec<&Item> {
+    items.iter().filter(|item| !item.is_comment()).collect()
 }
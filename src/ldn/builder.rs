@@ -0,0 +1,409 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Builds items from Rust code, either declaratively with the `ldn!` macro or one element at a
+//! time with a `Builder`. Built items have empty spans, write integers in decimal and strings with
+//! escape sequences.
+
+use std::convert::TryFrom;
+
+use super::ast::{Atom, Item, List, StringStyle};
+use super::integer::Integer;
+use super::pos::Span;
+use super::value::{TryFromItemError, Value};
+
+/// Builds an item from LDN written as Rust tokens.
+///
+/// Lists, vectors, maps and sets are written as in LDN, and so are `nil`, booleans, numbers,
+/// characters, strings and keywords. Identifiers and punctuation become symbols, while `(~ expr)`
+/// inserts anything that converts into an item:
+///
+/// ```
+/// use lithium::ldn;
+///
+/// let name = "x";
+/// let item = ldn!((define (~ ldn::builder::symbol(name)) [1 -2.5 "three" 'f'] {:a nil} #{true}));
+///
+/// assert_eq!(r#"(define x [1 -2.5 "three" \f] {:a nil} #{true})"#, item.to_string());
+/// ```
+///
+/// Since Rust doesn't keep track of spacing, `- 1` is read as `-1`, and names with characters that
+/// Rust doesn't accept in identifiers, like `user/id` or `set-name`, must be inserted with
+/// `symbol()` or `keyword()`.
+#[macro_export]
+macro_rules! ldn {
+    // Elements are expanded one token tree at a time, so that long collections don't add to the
+    // recursion depth, and the `:`, `-` and `#` prefixes are joined to them by `join_prefixes`.
+    (@items $($items:tt)*) => {
+        $crate::ldn::builder::join_prefixes(vec![$($crate::ldn!($items)),*])
+    };
+    ((~ $value:expr)) => {
+        $crate::ldn::Item::from($value)
+    };
+    (($($items:tt)*)) => {
+        $crate::ldn::builder::list($crate::ldn!(@items $($items)*))
+    };
+    ([$($items:tt)*]) => {
+        $crate::ldn::builder::vector($crate::ldn!(@items $($items)*))
+    };
+    ({$($items:tt)*}) => {
+        $crate::ldn::builder::map($crate::ldn!(@items $($items)*))
+    };
+    (# {$($items:tt)*}) => {
+        $crate::ldn::builder::set($crate::ldn!(@items $($items)*))
+    };
+    (: $name:ident) => {
+        $crate::ldn::builder::keyword(stringify!($name))
+    };
+    // A bare `-` is a symbol rather than the sign of a literal.
+    (-) => {
+        $crate::ldn::builder::symbol("-")
+    };
+    (- $value:literal) => {
+        $crate::ldn::Item::from(-$value)
+    };
+    (nil) => {
+        $crate::ldn::builder::nil()
+    };
+    ($value:literal) => {
+        $crate::ldn::Item::from($value)
+    };
+    ($name:tt) => {
+        $crate::ldn::builder::symbol(stringify!($name))
+    };
+}
+
+/// Builds a list, vector, map or set one element at a time.
+///
+/// ```
+/// use lithium::ldn::builder::{keyword, Builder};
+///
+/// let item = Builder::list()
+///     .symbol("define")
+///     .symbol("config")
+///     .item(Builder::map().entry(keyword("port"), 80).keyword("debug").item(false).build())
+///     .build();
+///
+/// assert_eq!("(define config {:port 80 :debug false})", item.to_string());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    build: fn(List) -> Item,
+    items: List,
+}
+
+impl Builder {
+    /// Starts building a list.
+    pub fn list() -> Self {
+        Self::new(list)
+    }
+
+    /// Starts building a vector.
+    pub fn vector() -> Self {
+        Self::new(vector)
+    }
+
+    /// Starts building a map, whose keys and values are appended in turn.
+    pub fn map() -> Self {
+        Self::new(map)
+    }
+
+    /// Starts building a set.
+    pub fn set() -> Self {
+        Self::new(set)
+    }
+
+    /// Appends an item, or anything that converts into one.
+    pub fn item<T>(mut self, item: T) -> Self
+    where
+        T: Into<Item>,
+    {
+        self.items.push(item.into());
+        self
+    }
+
+    /// Appends a key and a value.
+    pub fn entry<K, V>(self, key: K, value: V) -> Self
+    where
+        K: Into<Item>,
+        V: Into<Item>,
+    {
+        self.item(key).item(value)
+    }
+
+    /// Appends a symbol.
+    pub fn symbol(self, name: &str) -> Self {
+        self.item(symbol(name))
+    }
+
+    /// Appends a keyword, given without the leading colon.
+    pub fn keyword(self, name: &str) -> Self {
+        self.item(keyword(name))
+    }
+
+    /// Returns the built item.
+    pub fn build(self) -> Item {
+        (self.build)(self.items)
+    }
+
+    /// Returns the value of the built item. Fails if the item contains comments or, for maps, a
+    /// key without a value.
+    pub fn build_value(self) -> Result<Value, TryFromItemError> {
+        Value::try_from(&self.build())
+    }
+
+    fn new(build: fn(List) -> Item) -> Self {
+        Self {
+            build,
+            items: vec![],
+        }
+    }
+}
+
+impl From<Builder> for Item {
+    fn from(builder: Builder) -> Self {
+        builder.build()
+    }
+}
+
+/// Returns `nil`.
+pub fn nil() -> Item {
+    Item::Atom(Atom::Nil(Span::default()))
+}
+
+/// Returns a symbol, optionally qualified by a namespace (`http/get`).
+pub fn symbol(name: &str) -> Item {
+    Item::Atom(Atom::Symbol(name.into(), Span::default()))
+}
+
+/// Returns a keyword, given without the leading colon.
+pub fn keyword(name: &str) -> Item {
+    Item::Atom(Atom::Keyword(name.into(), Span::default()))
+}
+
+/// Returns a list of the given items.
+pub fn list(items: List) -> Item {
+    Item::List(items, Span::default())
+}
+
+/// Returns a vector of the given items.
+pub fn vector(items: List) -> Item {
+    Item::Vector(items, Span::default())
+}
+
+/// Returns a map of the given items, alternating keys and values.
+pub fn map(items: List) -> Item {
+    Item::Map(items, Span::default())
+}
+
+/// Returns a set of the given items.
+pub fn set(items: List) -> Item {
+    Item::Set(items, Span::default())
+}
+
+/// Joins the elements of a collection written with `ldn!` to the prefixes preceding them, reading
+/// `:` before a name as a keyword, `-` before a number as a negative number and `#` before a map
+/// as a set.
+#[doc(hidden)]
+pub fn join_prefixes(items: List) -> List {
+    let mut ret: List = vec![];
+
+    for item in items {
+        let prefix = match ret.last() {
+            Some(Item::Atom(Atom::Symbol(prefix, _))) => prefix.as_str(),
+            _ => "",
+        };
+        let joined = match (prefix, item) {
+            (":", Item::Atom(Atom::Symbol(name, _))) => keyword(&name),
+            (":", Item::Atom(Atom::Bool(v, _))) => keyword(&v.to_string()),
+            (":", Item::Atom(Atom::Nil(_))) => keyword("nil"),
+            ("-", Item::Atom(Atom::Integer(v, _, _))) => Item::from(-v),
+            ("-", Item::Atom(Atom::Float(v, _, _))) => Item::from(-v),
+            ("#", Item::Map(items, _)) => set(items),
+            (_, item) => {
+                ret.push(item);
+                continue;
+            }
+        };
+
+        ret.pop();
+        ret.push(joined);
+    }
+
+    ret
+}
+
+impl From<bool> for Item {
+    fn from(v: bool) -> Self {
+        Item::Atom(Atom::Bool(v, Span::default()))
+    }
+}
+
+impl From<char> for Item {
+    fn from(v: char) -> Self {
+        Item::Atom(Atom::Char(v, Span::default()))
+    }
+}
+
+impl From<f32> for Item {
+    fn from(v: f32) -> Self {
+        Item::from(f64::from(v))
+    }
+}
+
+impl From<f64> for Item {
+    fn from(v: f64) -> Self {
+        Item::Atom(Atom::Float(v, None, Span::default()))
+    }
+}
+
+impl From<i32> for Item {
+    fn from(v: i32) -> Self {
+        Item::from(Integer::from(v))
+    }
+}
+
+impl From<i64> for Item {
+    fn from(v: i64) -> Self {
+        Item::from(Integer::from(v))
+    }
+}
+
+impl From<u32> for Item {
+    fn from(v: u32) -> Self {
+        Item::from(Integer::from(i64::from(v)))
+    }
+}
+
+impl From<u64> for Item {
+    fn from(v: u64) -> Self {
+        Item::from(Integer::from(v))
+    }
+}
+
+impl From<Integer> for Item {
+    fn from(v: Integer) -> Self {
+        Item::Atom(Atom::Integer(v, None, Span::default()))
+    }
+}
+
+impl From<&str> for Item {
+    fn from(v: &str) -> Self {
+        Item::from(String::from(v))
+    }
+}
+
+impl From<String> for Item {
+    fn from(v: String) -> Self {
+        Item::Atom(Atom::String(v, StringStyle::Plain, Span::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::ast::Quote;
+    use super::super::options::Options;
+    use super::super::parser::Parser;
+
+    fn parse(input: &str) -> Item {
+        Parser::from(input)
+            .with_options(Options::extended())
+            .parse()
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn macro_items() {
+        let x = 42;
+
+        let item = crate::ldn!((
+            + 1 -2 3.5 -0.5 true false nil 'c' "s\n" :kw sym
+            [] {:a (b)} #{:nil} (~ x) (~ Quote::Quote.symbol())
+        ));
+
+        assert!(item.eq_value(&parse(
+            r#"(+ 1 -2 3.5 -0.5 true false nil \c "s\n" :kw sym [] {:a (b)} #{:nil} 42 "quote")"#
+        )));
+
+        assert!(crate::ldn!((- a (- 1) -)).eq_value(&parse("(- a (-1) -)")));
+        assert!(crate::ldn!(-1).eq_value(&parse("-1")));
+    }
+
+    #[test]
+    fn macro_long_list() {
+        let item = crate::ldn!((
+            0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9
+            0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9
+            0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9
+            0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9
+            0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9
+        ));
+
+        match item {
+            Item::List(items, _) => assert_eq!(200, items.len()),
+            _ => panic!("expected a list"),
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let item = Builder::list()
+            .symbol("a")
+            .item(Builder::vector().item(1).item(u64::MAX))
+            .item(Builder::set().keyword("b").build())
+            .build();
+
+        assert!(item.eq_value(&parse("(a [1 18446744073709551615] #{:b})")));
+
+        assert_eq!(
+            Ok(Value::Map(
+                vec![(Value::String("a".into()), Value::Nil)]
+                    .into_iter()
+                    .collect()
+            )),
+            Builder::map().entry("a", nil()).build_value()
+        );
+    }
+
+    #[test]
+    fn display() {
+        let input = concat!(
+            "(a ; one\n",
+            " #| two |# #_b 0x10 1/2 #\"r\"# #inst \"i\" '(c `d ,e ,@f)\n",
+            " \"\"\"\n",
+            "   text\n",
+            "   block\n",
+            " \"\"\")"
+        );
+
+        assert_eq!(
+            concat!(
+                "(a ; one\n",
+                "#| two |# #_b 0x10 1/2 #\"r\"# #inst \"i\" '(c `d ,e ,@f) \"  text\\n  block\\n\")"
+            ),
+            parse(input).to_string()
+        );
+
+        assert_eq!(
+            "{:a [1 2.5 \\space]}",
+            Value::try_from(&parse("{:a [1 2.5 \\space]}"))
+                .unwrap()
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,1282 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Lossless concrete syntax tree (CST) for LDN documents.
+//!
+//! The tree is split in two layers:
+//!
+//! - The *green* tree (`GreenNode`, `GreenToken`) is immutable, cheap to clone and does not know
+//!   about absolute positions. Each token stores its exact source text, trivia included, so that
+//!   concatenating all tokens yields the original document byte-for-byte.
+//! - The *red* tree (`SyntaxNode`, `SyntaxToken`) is a thin view over the green tree that adds
+//!   parent pointers and absolute offsets and positions, computed on demand.
+//!
+//! Editing a node produces a new green root that shares every untouched subtree with the old one.
+//! Green trees are thread-safe, so they can be kept around in shared state.
+
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::ast::{Item, List, Quote};
+use super::error::{Error, Result};
+use super::iter::PositionIterator;
+use super::options::Options;
+use super::parser::{
+    check_map, check_set, is_alpha, is_closing, is_newline, is_qualified_symbol, is_whitespace,
+    Parser,
+};
+use super::pos::{Position, Span};
+
+/// The kind of a node or token in the concrete syntax tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // Tokens
+    Whitespace,
+    Comment,
+    BlockComment,
+    /// The `#_` prefix of a datum comment.
+    Discard,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    /// The `{` opening a map.
+    LBrace,
+    /// The `#{` opening a set.
+    HashLBrace,
+    /// The `}` closing a map or a set.
+    RBrace,
+    /// A quote shorthand: `'`, `` ` ``, `,` or `,@`.
+    Quote,
+    /// A tag, along with the leading hash (`#inst`).
+    Tag,
+    String,
+    /// Integers, keywords, symbols and anything else up to the next delimiter.
+    Atom,
+
+    // Nodes
+    Root,
+    List,
+    Vector,
+    Map,
+    Set,
+    /// A quote shorthand followed by the quoted element, along with any whitespace and comments
+    /// between them.
+    Quoted,
+    /// A tag followed by the tagged element, along with any whitespace and comments between them.
+    Tagged,
+    /// The `#_` prefix followed by the commented element, along with any whitespace and comments
+    /// between them.
+    DatumComment,
+}
+
+impl SyntaxKind {
+    /// Returns `true` for tokens that carry no meaning, i.e. whitespace.
+    pub fn is_trivia(self) -> bool {
+        self == SyntaxKind::Whitespace
+    }
+
+    /// Returns `true` for line, block and datum comments.
+    pub fn is_comment(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Comment | SyntaxKind::BlockComment | SyntaxKind::DatumComment
+        )
+    }
+
+    /// Returns `true` for the tokens closing lists and collections.
+    pub fn is_closing(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::RParen | SyntaxKind::RBracket | SyntaxKind::RBrace
+        )
+    }
+
+    /// Returns `true` for the nodes made of a prefix and the element following it: datum
+    /// comments, quote shorthands and tagged elements.
+    pub fn is_prefixed(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::DatumComment | SyntaxKind::Quoted | SyntaxKind::Tagged
+        )
+    }
+
+    /// Returns the token closing a node of this kind, if it's a list or a collection.
+    pub fn closing(self) -> Option<SyntaxKind> {
+        match self {
+            SyntaxKind::List => Some(SyntaxKind::RParen),
+            SyntaxKind::Vector => Some(SyntaxKind::RBracket),
+            SyntaxKind::Map | SyntaxKind::Set => Some(SyntaxKind::RBrace),
+            _ => None,
+        }
+    }
+}
+
+/// A leaf of the green tree, holding its exact source text.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    /// Creates a new token of the given kind.
+    pub fn new(kind: SyntaxKind, text: String) -> Self {
+        Self { kind, text }
+    }
+
+    /// Returns the kind of the token.
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Returns the source text of the token.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// An inner node of the green tree.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    /// Creates a new node of the given kind with the given children.
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+
+        Self {
+            kind,
+            len,
+            children,
+        }
+    }
+
+    /// Returns the kind of the node.
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Returns the length of the node's text, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the node doesn't cover any text.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the children of the node.
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Returns a copy of this node where the child at `index` has been replaced. Other children
+    /// are shared with this node.
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> Self {
+        let mut children = self.children.clone();
+        children[index] = child;
+
+        Self::new(self.kind, children)
+    }
+
+    fn write_text(&self, buf: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(buf),
+                GreenElement::Token(token) => buf.push_str(&token.text),
+            }
+        }
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = String::with_capacity(self.len);
+        self.write_text(&mut buf);
+
+        f.write_str(&buf)
+    }
+}
+
+/// Either a node or a token of the green tree.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    /// Returns the kind of the element.
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            GreenElement::Node(node) => node.kind,
+            GreenElement::Token(token) => token.kind,
+        }
+    }
+
+    /// Returns the length of the element's text, in bytes.
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    /// Returns `true` if the element doesn't cover any text.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<GreenNode> for GreenElement {
+    fn from(node: GreenNode) -> Self {
+        GreenElement::Node(Arc::new(node))
+    }
+}
+
+impl From<GreenToken> for GreenElement {
+    fn from(token: GreenToken) -> Self {
+        GreenElement::Token(Arc::new(token))
+    }
+}
+
+/// A node of the red tree: a green node plus its parent and absolute location in the document.
+#[derive(Clone, Debug)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+#[derive(Debug)]
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    index: usize,
+    offset: usize,
+    start: Position,
+    /// The options used when deriving the AST, shared by the whole tree.
+    options: Rc<Options>,
+}
+
+impl SyntaxNode {
+    /// Creates the red root for the given green tree.
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        Self::new_root_at(green, 0, Position::default())
+    }
+
+    /// Creates the red root for a green tree whose text begins at the given byte `offset` and
+    /// position of a larger document.
+    pub fn new_root_at(green: Arc<GreenNode>, offset: usize, start: Position) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            index: 0,
+            offset,
+            start,
+            options: Rc::new(Options::default()),
+        }))
+    }
+
+    /// Returns this root with the options used when deriving the AST, which should be the ones
+    /// the tree was parsed with. Strict LDN is assumed by default.
+    pub fn with_options(self, options: Options) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green: self.0.green.clone(),
+            parent: self.0.parent.clone(),
+            index: self.0.index,
+            offset: self.0.offset,
+            start: self.0.start.clone(),
+            options: Rc::new(options),
+        }))
+    }
+
+    /// Returns the kind of the node.
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    /// Returns the underlying green node.
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    /// Returns the parent node, or `None` for the root.
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// Returns the byte offset of the node from the beginning of the document.
+    pub fn offset(&self) -> usize {
+        self.0.offset
+    }
+
+    /// Returns the `Span` covered by the node.
+    pub fn span(&self) -> Span {
+        let start = self.0.start.clone();
+        let end = advance_node(&start, &self.0.green);
+
+        Span::new(start, end)
+    }
+
+    /// Returns the source text of the node.
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    /// Returns the children of the node, tokens included.
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut start = self.0.start.clone();
+        let mut ret = Vec::with_capacity(self.0.green.children.len());
+
+        for (index, child) in self.0.green.children.iter().enumerate() {
+            let next_start;
+
+            ret.push(match child {
+                GreenElement::Node(green) => {
+                    next_start = advance_node(&start, green);
+
+                    SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                        green: green.clone(),
+                        parent: Some(self.clone()),
+                        index,
+                        offset,
+                        start,
+                        options: self.0.options.clone(),
+                    })))
+                }
+                GreenElement::Token(green) => {
+                    next_start = advance(&start, &green.text);
+
+                    SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        parent: self.clone(),
+                        index,
+                        offset,
+                        start,
+                    })
+                }
+            });
+
+            offset += child.len();
+            start = next_start;
+        }
+
+        ret
+    }
+
+    /// Returns the child nodes of the node, skipping tokens.
+    pub fn child_nodes(&self) -> Vec<SyntaxNode> {
+        self.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// Replaces this node with `replacement` and returns the new green root. The rest of the tree
+    /// is shared with the current one and is left untouched.
+    pub fn replace_with(&self, replacement: GreenNode) -> Arc<GreenNode> {
+        match self.parent() {
+            None => Arc::new(replacement),
+            Some(parent) => parent.replace_child(self.0.index, replacement.into()),
+        }
+    }
+
+    /// Builds the AST for this node, stopping at the first error.
+    ///
+    /// For the root node this returns all top-level items, otherwise it returns a list with a
+    /// single item.
+    pub fn to_items(&self) -> Result<List> {
+        let mut builder = AstBuilder::new(false, &self.0.options);
+
+        builder.build(self).map(|item| match item {
+            Some(Item::List(items, _)) if self.kind() == SyntaxKind::Root => items,
+            Some(item) => vec![item],
+            None => vec![],
+        })
+    }
+
+    /// Builds the AST for this node, keeping malformed tokens as `Item::Error` like
+    /// `Parser::parse_recovering()` does, and returns it along with every error found.
+    pub fn to_items_recovering(&self) -> (List, Vec<Error>) {
+        let mut builder = AstBuilder::new(true, &self.0.options);
+
+        let items = match builder.build(self).expect("recovering builder never fails") {
+            Some(Item::List(items, _)) if self.kind() == SyntaxKind::Root => items,
+            Some(item) => vec![item],
+            None => vec![],
+        };
+
+        (items, builder.errors)
+    }
+
+    fn replace_child(&self, index: usize, child: GreenElement) -> Arc<GreenNode> {
+        self.replace_with(self.0.green.replace_child(index, child))
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.offset == other.0.offset && Arc::ptr_eq(&self.0.green, &other.0.green)
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.green.fmt(f)
+    }
+}
+
+/// A token of the red tree: a green token plus its parent and absolute location in the document.
+#[derive(Clone, Debug)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    parent: SyntaxNode,
+    index: usize,
+    offset: usize,
+    start: Position,
+}
+
+impl SyntaxToken {
+    /// Returns the kind of the token.
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    /// Returns the underlying green token.
+    pub fn green(&self) -> &Arc<GreenToken> {
+        &self.green
+    }
+
+    /// Returns the node containing this token.
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    /// Returns the byte offset of the token from the beginning of the document.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the `Span` covered by the token.
+    pub fn span(&self) -> Span {
+        Span::new(self.start.clone(), advance(&self.start, &self.green.text))
+    }
+
+    /// Returns the source text of the token.
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    /// Replaces this token with `replacement` and returns the new green root. The rest of the
+    /// tree is shared with the current one and is left untouched.
+    pub fn replace_with(&self, replacement: GreenToken) -> Arc<GreenNode> {
+        self.parent
+            .replace_child(self.index, GreenElement::from(replacement))
+    }
+}
+
+/// Either a node or a token of the red tree.
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+    /// Returns the kind of the element.
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            SyntaxElement::Node(node) => node.kind(),
+            SyntaxElement::Token(token) => token.kind(),
+        }
+    }
+
+    /// Returns the `Span` covered by the element.
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.span(),
+            SyntaxElement::Token(token) => token.span(),
+        }
+    }
+}
+
+/// Parses strict LDN `text` into a lossless concrete syntax tree. Never fails: malformed input
+/// ends up in `Atom` tokens, lists left open are closed at the end of the text, and stray closing
+/// parens appear as `RParen` tokens under the root. Errors are reported when deriving the AST.
+pub fn parse(text: &str) -> SyntaxNode {
+    parse_with_options(text, Options::default())
+}
+
+/// Parses `text` like `parse()`, in the dialect selected by `options`. Tag handlers aren't run
+/// when deriving the AST, and columns always count tabs as a single character.
+pub fn parse_with_options(text: &str, options: Options) -> SyntaxNode {
+    let mut lexer = Lexer {
+        text,
+        offset: 0,
+        options: &options,
+    };
+    let children = lexer.parse_elements(None);
+
+    SyntaxNode::new_root(Arc::new(GreenNode::new(SyntaxKind::Root, children))).with_options(options)
+}
+
+//
+// Private
+//
+
+/// Returns the position reached after reading `text` from `start`.
+fn advance(start: &Position, text: &str) -> Position {
+    let mut iter = PositionIterator::with_position(text.bytes(), start.clone());
+    iter.by_ref().last();
+
+    iter.pos().clone()
+}
+
+/// Returns the position reached after reading the text of `node` from `start`.
+fn advance_node(start: &Position, node: &GreenNode) -> Position {
+    node.children
+        .iter()
+        .fold(start.clone(), |pos, child| match child {
+            GreenElement::Node(node) => advance_node(&pos, node),
+            GreenElement::Token(token) => advance(&pos, &token.text),
+        })
+}
+
+/// Returns the kind of the innermost list or collection containing `node`, or `Root` at the top
+/// level.
+fn enclosing_collection(node: &SyntaxNode) -> SyntaxKind {
+    match node.parent() {
+        Some(parent) if node.kind().is_prefixed() => enclosing_collection(parent),
+        _ => node.kind(),
+    }
+}
+
+/// Returns `true` if `ch` ends a token, that is if it's whitespace or a closing delimiter.
+fn is_delimiter(ch: u8, collections: bool) -> bool {
+    is_whitespace(char::from(ch)) || is_closing(char::from(ch), collections)
+}
+
+/// The opening and closing delimiter of a text block.
+const TEXT_BLOCK_DELIMITER: &[u8] = b"\"\"\"";
+
+/// Splits text into tokens and builds the green tree. The token boundaries match the ones used by
+/// `Parser`, so that each token can be handed over to it when deriving the AST.
+struct Lexer<'a> {
+    text: &'a str,
+    offset: usize,
+    options: &'a Options,
+}
+
+impl<'a> Lexer<'a> {
+    fn parse_elements(&mut self, closing: Option<u8>) -> Vec<GreenElement> {
+        let mut ret = vec![];
+
+        while let Some(element) = self.parse_element(closing) {
+            ret.push(element);
+        }
+
+        ret
+    }
+
+    /// Parses the next element, or returns `None` at the end of the text or at the `closing`
+    /// delimiter of the enclosing list or collection.
+    fn parse_element(&mut self, closing: Option<u8>) -> Option<GreenElement> {
+        let ch = self.peek()?;
+        let start = self.offset;
+        let collections = self.options.collections;
+
+        let kind = match ch {
+            _ if is_whitespace(char::from(ch)) => {
+                self.bump_while(|ch| is_whitespace(char::from(ch)));
+                SyntaxKind::Whitespace
+            }
+            b';' => {
+                self.bump_while(|ch| !is_newline(char::from(ch)));
+                SyntaxKind::Comment
+            }
+            b'#' if self.peek_at(1) == Some(b'|') => {
+                self.lex_block_comment();
+                SyntaxKind::BlockComment
+            }
+            b'#' if self.peek_at(1) == Some(b'_') => {
+                let node =
+                    self.parse_prefixed(SyntaxKind::DatumComment, SyntaxKind::Discard, 2, closing);
+                return Some(node.into());
+            }
+            b'#' if self.raw_string_hashes().is_some() => {
+                self.lex_raw_string();
+                SyntaxKind::String
+            }
+            b'#' if collections && self.peek_at(1) == Some(b'{') => {
+                let node = self.parse_collection(SyntaxKind::Set, SyntaxKind::HashLBrace, 2);
+                return Some(node.into());
+            }
+            b'#' if self.tag_len().is_some() => {
+                let len = self.tag_len().expect("tag");
+                let node = self.parse_prefixed(SyntaxKind::Tagged, SyntaxKind::Tag, len, closing);
+                return Some(node.into());
+            }
+            b'\'' | b'`' | b',' if self.options.quotes => {
+                let len = if ch == b',' && self.peek_at(1) == Some(b'@') {
+                    2
+                } else {
+                    1
+                };
+                let node = self.parse_prefixed(SyntaxKind::Quoted, SyntaxKind::Quote, len, closing);
+                return Some(node.into());
+            }
+            b'(' => {
+                return Some(
+                    self.parse_collection(SyntaxKind::List, SyntaxKind::LParen, 1)
+                        .into(),
+                )
+            }
+            b'[' if collections => {
+                let node = self.parse_collection(SyntaxKind::Vector, SyntaxKind::LBracket, 1);
+                return Some(node.into());
+            }
+            b'{' if collections => {
+                let node = self.parse_collection(SyntaxKind::Map, SyntaxKind::LBrace, 1);
+                return Some(node.into());
+            }
+            _ if Some(ch) == closing => return None,
+            b')' => {
+                self.offset += 1;
+                SyntaxKind::RParen
+            }
+            b']' if collections => {
+                self.offset += 1;
+                SyntaxKind::RBracket
+            }
+            b'}' if collections => {
+                self.offset += 1;
+                SyntaxKind::RBrace
+            }
+            b'"' if self.text.as_bytes()[self.offset..].starts_with(TEXT_BLOCK_DELIMITER) => {
+                self.lex_text_block();
+                SyntaxKind::String
+            }
+            b'"' => {
+                self.lex_string();
+                SyntaxKind::String
+            }
+            _ => {
+                // The character following a backslash belongs to the character literal, even if
+                // it's a closing delimiter.
+                if ch == b'\\'
+                    && self
+                        .peek_at(1)
+                        .is_some_and(|ch| !is_whitespace(char::from(ch)))
+                {
+                    self.offset += 2;
+                }
+
+                self.bump_while(|ch| !is_delimiter(ch, collections));
+                SyntaxKind::Atom
+            }
+        };
+
+        Some(self.token(kind, start))
+    }
+
+    /// Parses a list or collection, whose opening delimiter is `len` bytes long. Collections left
+    /// open at the end of the text have no closing token.
+    fn parse_collection(&mut self, kind: SyntaxKind, opening: SyntaxKind, len: usize) -> GreenNode {
+        let start = self.offset;
+        self.offset += len;
+
+        let closing = match kind {
+            SyntaxKind::List => b')',
+            SyntaxKind::Vector => b']',
+            _ => b'}',
+        };
+
+        let mut children = vec![self.token(opening, start)];
+        children.extend(self.parse_elements(Some(closing)));
+
+        if self.peek() == Some(closing) {
+            let start = self.offset;
+            self.offset += 1;
+            children.push(self.token(kind.closing().expect("closing delimiter"), start));
+        }
+
+        GreenNode::new(kind, children)
+    }
+
+    /// Parses a datum comment, quote shorthand or tagged element, whose prefix is `len` bytes
+    /// long, up to and including the first element that is neither whitespace nor a comment.
+    fn parse_prefixed(
+        &mut self,
+        kind: SyntaxKind,
+        prefix: SyntaxKind,
+        len: usize,
+        closing: Option<u8>,
+    ) -> GreenNode {
+        let start = self.offset;
+        self.offset += len;
+
+        let mut children = vec![self.token(prefix, start)];
+
+        while let Some(element) = self.parse_element(closing) {
+            let kind = element.kind();
+            children.push(element);
+
+            if !kind.is_trivia() && !kind.is_comment() {
+                break;
+            }
+        }
+
+        GreenNode::new(kind, children)
+    }
+
+    /// Returns the length of the tag starting at the current offset, hash included, or `None` if
+    /// there's no valid tag there. Malformed tags are left to the atom branch, so that the parser
+    /// reports them.
+    fn tag_len(&self) -> Option<usize> {
+        if !self.options.tagged_elements {
+            return None;
+        }
+
+        let rest = &self.text[self.offset + 1..];
+        let len = rest
+            .bytes()
+            .take_while(|&ch| !is_delimiter(ch, self.options.collections))
+            .count();
+
+        Some(len + 1).filter(|_| {
+            rest.chars().next().is_some_and(is_alpha) && is_qualified_symbol(&rest[..len])
+        })
+    }
+
+    /// Consumes a block comment up to and including the closing delimiter matching the opening
+    /// one, skipping nested block comments.
+    fn lex_block_comment(&mut self) {
+        self.offset += 2;
+
+        let mut depth = 1;
+
+        while let Some(ch) = self.peek() {
+            self.offset += 1;
+
+            match (ch, self.peek()) {
+                (b'#', Some(b'|')) => {
+                    self.offset += 1;
+                    depth += 1;
+                }
+                (b'|', Some(b'#')) => {
+                    self.offset += 1;
+                    depth -= 1;
+
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Consumes a string up to and including the closing quotation mark. A backslash escapes the
+    /// character following it, so escaped quotation marks do not terminate the string.
+    fn lex_string(&mut self) {
+        self.offset += 1;
+
+        while let Some(ch) = self.peek() {
+            self.offset += 1;
+
+            match ch {
+                b'"' => break,
+                b'\\' if self.peek().is_some() => self.offset += 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Consumes a text block up to and including the closing delimiter. Like in strings, a
+    /// backslash escapes the character following it.
+    fn lex_text_block(&mut self) {
+        self.offset += TEXT_BLOCK_DELIMITER.len();
+
+        while let Some(ch) = self.peek() {
+            if self.text.as_bytes()[self.offset..].starts_with(TEXT_BLOCK_DELIMITER) {
+                self.offset += TEXT_BLOCK_DELIMITER.len();
+                break;
+            }
+
+            self.offset += 1;
+
+            if ch == b'\\' && self.peek().is_some() {
+                self.offset += 1;
+            }
+        }
+    }
+
+    /// Returns the number of hashes before the opening quotation mark of a raw string starting at
+    /// the current offset, or `None` if there's no raw string there.
+    fn raw_string_hashes(&self) -> Option<usize> {
+        let hashes = self.text.as_bytes()[self.offset..]
+            .iter()
+            .take_while(|&&ch| ch == b'#')
+            .count();
+
+        Some(hashes).filter(|_| self.peek_at(hashes) == Some(b'"'))
+    }
+
+    /// Consumes a raw string up to and including the closing delimiter, a quotation mark
+    /// followed by as many hashes as the opening one.
+    fn lex_raw_string(&mut self) {
+        let hashes = self.raw_string_hashes().expect("raw string");
+        let closing = format!("\"{}", "#".repeat(hashes));
+
+        self.offset += hashes + 1;
+
+        match self.text[self.offset..].find(&closing) {
+            Some(index) => self.offset += index + closing.len(),
+            None => self.offset = self.text.len(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.text.as_bytes().get(self.offset + n).cloned()
+    }
+
+    fn bump_while<F: Fn(u8) -> bool>(&mut self, p: F) {
+        while let Some(ch) = self.peek() {
+            if !p(ch) {
+                break;
+            }
+
+            self.offset += 1;
+        }
+    }
+
+    fn token(&self, kind: SyntaxKind, start: usize) -> GreenElement {
+        GreenToken::new(kind, self.text[start..self.offset].to_string()).into()
+    }
+}
+
+/// Derives the AST from the red tree.
+struct AstBuilder {
+    recovering: bool,
+    errors: Vec<Error>,
+    options: Options,
+}
+
+impl AstBuilder {
+    fn new(recovering: bool, options: &Options) -> Self {
+        Self {
+            recovering,
+            errors: vec![],
+            // Positions come from the red tree, which counts tabs as a single column.
+            options: Options {
+                tab_width: None,
+                ..options.clone()
+            },
+        }
+    }
+
+    /// Builds the item for the given node. The root is returned as a list of top-level items.
+    fn build(&mut self, node: &SyntaxNode) -> Result<Option<Item>> {
+        if node.kind().is_prefixed() {
+            return self.build_prefixed(node);
+        }
+
+        let closing = node.kind().closing();
+        let mut items = vec![];
+        let mut closed = node.kind() == SyntaxKind::Root;
+
+        for child in node.children() {
+            let item = match child {
+                SyntaxElement::Node(node) => self.build(&node)?,
+                SyntaxElement::Token(token) => match token.kind() {
+                    SyntaxKind::Whitespace
+                    | SyntaxKind::LParen
+                    | SyntaxKind::LBracket
+                    | SyntaxKind::LBrace
+                    | SyntaxKind::HashLBrace => None,
+                    kind if Some(kind) == closing => {
+                        closed = true;
+                        None
+                    }
+                    _ => Some(self.build_token(&token)?),
+                },
+            };
+
+            items.extend(item);
+        }
+
+        if !closed {
+            self.fail(Error::UnbalancedParentheses(node.span().end))?;
+        }
+
+        let span = node.span();
+
+        let err = match node.kind() {
+            SyntaxKind::Map => check_map(&items, &span),
+            SyntaxKind::Set => check_set(&items),
+            _ => None,
+        };
+
+        if let Some(err) = err {
+            self.fail(err)?;
+        }
+
+        Ok(Some(match node.kind() {
+            SyntaxKind::Vector => Item::Vector(items, span),
+            SyntaxKind::Map => Item::Map(items, span),
+            SyntaxKind::Set => Item::Set(items, span),
+            _ => Item::List(items, span),
+        }))
+    }
+
+    /// Builds the datum comment, quoted item or tagged element for the given node. Comments
+    /// found between the prefix and its value are discarded, like the parser does.
+    fn build_prefixed(&mut self, node: &SyntaxNode) -> Result<Option<Item>> {
+        let mut prefix = node.span();
+        let mut prefix_text = String::new();
+        let mut value = None;
+
+        for child in node.children() {
+            let item = match child {
+                SyntaxElement::Node(node) => self.build(&node)?,
+                SyntaxElement::Token(token) => match token.kind() {
+                    SyntaxKind::Whitespace => None,
+                    SyntaxKind::Discard | SyntaxKind::Quote | SyntaxKind::Tag => {
+                        prefix = token.span();
+                        prefix_text = token.text().to_string();
+                        None
+                    }
+                    _ => Some(self.build_token(&token)?),
+                },
+            };
+
+            value = item.filter(|item| !item.is_comment()).or(value);
+        }
+
+        let value = match value {
+            Some(value) => Box::new(value),
+            None => {
+                self.fail(match node.kind() {
+                    SyntaxKind::Tagged => Error::TagWithoutValue(prefix),
+                    SyntaxKind::Quoted => Error::QuoteWithoutValue(prefix),
+                    _ => Error::DatumCommentWithoutValue(prefix),
+                })?;
+
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(match node.kind() {
+            SyntaxKind::Tagged => Item::Tagged(prefix_text[1..].to_string(), value, node.span()),
+            SyntaxKind::Quoted => {
+                let quote = match prefix_text.as_str() {
+                    "'" => Quote::Quote,
+                    "`" => Quote::Quasiquote,
+                    ",@" => Quote::UnquoteSplicing,
+                    _ => Quote::Unquote,
+                };
+
+                Item::Quoted(quote, value, node.span())
+            }
+            _ => Item::DatumComment(value, node.span()),
+        }))
+    }
+
+    /// Builds the item for the given token by running it through the parser. Closing delimiters
+    /// reaching this point are stray ones, and are reported like the parser does.
+    fn build_token(&mut self, token: &SyntaxToken) -> Result<Item> {
+        let span = token.span();
+
+        if token.kind().is_closing() {
+            let ch = char::from(token.text().as_bytes()[0]);
+
+            let err = match enclosing_collection(token.parent()) {
+                SyntaxKind::Root => Error::UnbalancedParentheses(span.start),
+                _ => Error::MismatchedDelimiter(ch, span.start),
+            };
+
+            return self.error(token, err);
+        }
+
+        let mut parser =
+            Parser::with_start(token.text().bytes(), span.start).with_options(self.options.clone());
+
+        match parser.parse() {
+            Ok(mut items) => Ok(items.remove(0)),
+            Err(err) => self.error(token, err),
+        }
+    }
+
+    /// Records the given error for a malformed token, returning an `Item::Error` in its place.
+    fn error(&mut self, token: &SyntaxToken, err: Error) -> Result<Item> {
+        self.fail(err)?;
+
+        Ok(Item::Error(token.text().to_string(), token.span()))
+    }
+
+    fn fail(&mut self, err: Error) -> Result<()> {
+        if !self.recovering {
+            return Err(err);
+        }
+
+        self.errors.push(err);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::ast::Atom;
+    use super::super::integer::Integer;
+    use super::super::pos::{pos, span};
+
+    #[test]
+    fn round_trip() {
+        let text = "  ; comment \n(foo  (bar\t \"b\\\"az\")) x$ )\n(";
+
+        assert_eq!(text, parse(text).to_string());
+    }
+
+    #[test]
+    fn tokens() {
+        let root = parse("(a \"b\") ;c");
+        let kinds = root
+            .children()
+            .iter()
+            .map(SyntaxElement::kind)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                SyntaxKind::List,
+                SyntaxKind::Whitespace,
+                SyntaxKind::Comment
+            ],
+            kinds
+        );
+
+        let list = &root.child_nodes()[0];
+        let kinds = list
+            .children()
+            .iter()
+            .map(|e| (e.kind(), e.span()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (SyntaxKind::LParen, span(0, 0, 0, 1)),
+                (SyntaxKind::Atom, span(0, 1, 0, 2)),
+                (SyntaxKind::Whitespace, span(0, 2, 0, 3)),
+                (SyntaxKind::String, span(0, 3, 0, 6)),
+                (SyntaxKind::RParen, span(0, 6, 0, 7)),
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn to_items() {
+        let text = "; foo\n(a (1 \"x\")\n  :b)";
+
+        assert_eq!(Parser::from(text).parse(), parse(text).to_items(),);
+    }
+
+    #[test]
+    fn to_items_errors() {
+        assert_eq!(
+            Err(Error::UnbalancedParentheses(pos(0, 8))),
+            parse("(1 (2) 3").to_items()
+        );
+
+        assert_eq!(
+            (
+                vec![
+                    Item::List(
+                        vec![Item::Error("01".into(), span(0, 1, 0, 3))],
+                        span(0, 0, 0, 4)
+                    ),
+                    Item::Error(")".into(), span(0, 5, 0, 6)),
+                ],
+                vec![
+                    Error::IntegerLeadingZero("01".into(), span(0, 1, 0, 3)),
+                    Error::UnbalancedParentheses(pos(0, 5)),
+                ]
+            ),
+            parse("(01) )").to_items_recovering()
+        );
+    }
+
+    #[test]
+    fn to_items_comments() {
+        let text = "#| a #| b |# |#\n(a #_ ; c\n (b) #_ #_ c d)";
+
+        assert_eq!(SyntaxKind::BlockComment, parse(text).children()[0].kind());
+        assert_eq!(Parser::from(text).parse(), parse(text).to_items());
+
+        assert_eq!(
+            (
+                vec![
+                    Item::List(vec![], span(0, 0, 0, 4)),
+                    Item::Error("#| a".into(), span(1, 0, 1, 4)),
+                ],
+                vec![
+                    Error::DatumCommentWithoutValue(span(0, 1, 0, 3)),
+                    Error::UnterminatedBlockComment(span(1, 0, 1, 4)),
+                ]
+            ),
+            parse("(#_)\n#| a").to_items_recovering()
+        );
+    }
+
+    #[test]
+    fn to_items_strings() {
+        let text = "(#\"a\"b\"# ##\"(\"#)\"## \"\"\"\n  \\\"\"\")\n  \"\"\" \"\")";
+        let list = &parse(text).child_nodes()[0];
+        let strings = list
+            .children()
+            .iter()
+            .filter(|e| e.kind() == SyntaxKind::String)
+            .count();
+
+        assert_eq!(4, strings);
+        assert_eq!(Parser::from(text).parse(), parse(text).to_items());
+    }
+
+    #[test]
+    fn to_items_extended() {
+        let text = "[a {:b #{1 2.5}} #_ ; c\n 'd `(,e ,@f)] #inst \"x\" #a #| b |# [3/4]";
+        let parser = || Parser::from(text).with_options(Options::extended());
+        let root = parse_with_options(text, Options::extended());
+
+        assert_eq!(text, root.to_string());
+        assert_eq!(
+            vec![
+                SyntaxKind::Vector,
+                SyntaxKind::Whitespace,
+                SyntaxKind::Tagged,
+                SyntaxKind::Whitespace,
+                SyntaxKind::Tagged,
+            ],
+            root.children()
+                .iter()
+                .map(SyntaxElement::kind)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(parser().parse(), root.to_items());
+
+        // Strict LDN reports the extensions as malformed atoms, like the parser does.
+        assert_eq!(Parser::from(text).parse(), parse(text).to_items());
+    }
+
+    #[test]
+    fn to_items_extended_errors() {
+        for text in &[
+            "[a)",
+            "(a]",
+            "#{1 1}",
+            "{:a}",
+            "{:a 1 :a 2}",
+            "}",
+            "[#_]",
+            "(')",
+            "(#a)",
+            "#a\"b\" c",
+            "#1 a",
+            "#{a",
+        ] {
+            let root = parse_with_options(text, Options::extended());
+
+            assert_eq!(text, &root.to_string());
+            assert_eq!(
+                Parser::from(*text)
+                    .with_options(Options::extended())
+                    .parse(),
+                root.to_items(),
+                "{:?}",
+                text
+            );
+        }
+
+        assert_eq!(
+            (
+                vec![Item::Vector(
+                    vec![
+                        Item::Atom(Atom::Symbol("a".into(), span(0, 1, 0, 2))),
+                        Item::Error(")".into(), span(0, 3, 0, 4)),
+                    ],
+                    span(0, 0, 0, 6)
+                )],
+                vec![Error::MismatchedDelimiter(')', pos(0, 3))]
+            ),
+            parse_with_options("[a ) ]", Options::extended()).to_items_recovering()
+        );
+    }
+
+    #[test]
+    fn replace_with() {
+        let root = parse("(a b)\n\n(c d)");
+        let second = &root.child_nodes()[1];
+        let replacement = parse("(e)").green().children()[0].clone();
+
+        let new_root = match replacement {
+            GreenElement::Node(node) => second.replace_with(Arc::try_unwrap(node).unwrap()),
+            GreenElement::Token(_) => unreachable!(),
+        };
+
+        assert_eq!("(a b)\n\n(e)", new_root.to_string());
+        // Untouched subtrees are shared.
+        assert_eq!(&root.green().children()[0], &new_root.children()[0]);
+
+        let token = match &SyntaxNode::new_root(new_root).child_nodes()[0].children()[1] {
+            SyntaxElement::Token(token) => token.clone(),
+            SyntaxElement::Node(_) => unreachable!(),
+        };
+
+        let new_root = token.replace_with(GreenToken::new(SyntaxKind::Atom, "42".into()));
+
+        assert_eq!("(42 b)\n\n(e)", new_root.to_string());
+        assert_eq!(
+            Ok(vec![
+                Item::List(
+                    vec![
+                        Item::Atom(Atom::Integer(Integer::from(42), None, span(0, 1, 0, 3))),
+                        Item::Atom(Atom::Symbol("b".into(), span(0, 4, 0, 5))),
+                    ],
+                    span(0, 0, 0, 6)
+                ),
+                Item::List(
+                    vec![Item::Atom(Atom::Symbol("e".into(), span(2, 1, 2, 2)))],
+                    span(2, 0, 2, 3)
+                ),
+            ]),
+            SyntaxNode::new_root(new_root).to_items()
+        );
+    }
+}
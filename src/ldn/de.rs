@@ -0,0 +1,525 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Deserializes Rust values from LDN items with serde.
+//!
+//! Items are read back following the mapping described in the `ser` module. Comments are skipped,
+//! and structs can be read from maps as well as from property lists. Errors carry the span of the
+//! offending item.
+
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::result;
+use std::vec;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Unexpected, Visitor};
+use serde::forward_to_deserialize_any;
+
+use super::ast::{Atom, Item};
+use super::error::Error as ParseError;
+use super::options::Options;
+use super::parser::Parser;
+use super::pos::Span;
+
+/// A specialized `Result` type for deserialization.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The error type for deserialization.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The document couldn't be parsed.
+    Parse(ParseError),
+    /// The items don't match the type being deserialized. Holds the message and the span of the
+    /// offending item.
+    Message(String, Span),
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(err) => err.fmt(f),
+            Error::Message(message, span) => write!(f, "{} {}", span, message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse(err) => Some(err),
+            Error::Message(..) => None,
+        }
+    }
+}
+
+/// Deserializes a value from an item.
+pub fn from_item<'de, T>(item: &'de Item) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    deserialize_seed(PhantomData, item)
+}
+
+/// Deserializes a value from an LDN document made of a single item, parsed with all the
+/// extensions enabled.
+pub fn from_str<T>(text: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let items = Parser::from(text)
+        .with_options(Options::extended())
+        .parse()?;
+    let mut values = items.iter().filter(|item| !item.is_comment());
+
+    match (values.next(), values.next()) {
+        (Some(item), None) => from_item(item),
+        (None, _) => Err(Error::Message("expected a value".into(), Span::default())),
+        (Some(_), Some(extra)) => Err(Error::Message(
+            "expected a single value".into(),
+            extra.span().clone(),
+        )),
+    }
+}
+
+/// A deserializer reading values from an item.
+pub struct Deserializer<'de> {
+    item: &'de Item,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(item: &'de Item) -> Self {
+        Self { item }
+    }
+
+    //
+    // Private
+    //
+
+    /// Attaches the span of the item to errors raised without one, for instance by visitors.
+    fn spanned<T>(&self, result: Result<T>) -> Result<T> {
+        result.map_err(|err| match err {
+            Error::Message(message, span) if span == Span::default() => {
+                Error::Message(message, self.item.span().clone())
+            }
+            err => err,
+        })
+    }
+
+    fn invalid_type<T>(&self, expected: &dyn de::Expected) -> Result<T> {
+        self.spanned(Err(de::Error::invalid_type(self.unexpected(), expected)))
+    }
+
+    /// Describes the item for error messages.
+    fn unexpected(&self) -> Unexpected<'de> {
+        match self.item {
+            Item::Atom(Atom::Bool(v, _)) => Unexpected::Bool(*v),
+            Item::Atom(Atom::Char(v, _)) => Unexpected::Char(*v),
+            Item::Atom(Atom::Float(v, _, _)) => Unexpected::Float(*v),
+            Item::Atom(Atom::Integer(v, _, _)) => match (v.as_i64(), v.as_u64()) {
+                (_, Some(v)) => Unexpected::Unsigned(v),
+                (Some(v), _) => Unexpected::Signed(v),
+                _ => Unexpected::Other("integer"),
+            },
+            Item::Atom(Atom::Keyword(..)) => Unexpected::Other("keyword"),
+            Item::Atom(Atom::Nil(_)) => Unexpected::Unit,
+            Item::Atom(Atom::Ratio(..)) => Unexpected::Other("ratio"),
+            Item::Atom(Atom::String(v, _, _)) => Unexpected::Str(v),
+            Item::Atom(Atom::Symbol(..)) => Unexpected::Other("symbol"),
+            Item::BlockComment(..) | Item::Comment(..) | Item::DatumComment(..) => {
+                Unexpected::Other("comment")
+            }
+            Item::Error(..) => Unexpected::Other("malformed item"),
+            Item::List(..) | Item::Set(..) | Item::Vector(..) => Unexpected::Seq,
+            Item::Map(..) => Unexpected::Map,
+            Item::Quoted(..) => Unexpected::Other("quoted item"),
+            Item::Tagged(..) => Unexpected::Other("tagged item"),
+        }
+    }
+
+    /// Visits the values of a list, vector or set as a sequence.
+    fn visit_seq<V>(&self, values: Vec<&'de Item>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut seq = SeqAccess::new(values);
+        let value = self.spanned(visitor.visit_seq(&mut seq))?;
+
+        match seq.iter.len() {
+            0 => Ok(value),
+            remaining => self.spanned(Err(de::Error::invalid_length(
+                seq.count + remaining,
+                &"fewer elements",
+            ))),
+        }
+    }
+
+    /// Visits the values of a map or of a property list, alternating keys and values.
+    fn visit_map<V>(&self, values: Vec<&'de Item>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if !values.len().is_multiple_of(2) {
+            return self.spanned(Err(de::Error::custom(
+                "property list with a key without a value",
+            )));
+        }
+
+        self.spanned(visitor.visit_map(&mut MapAccess::new(values)))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let value = match self.item {
+            Item::Atom(Atom::Bool(v, _)) => visitor.visit_bool(*v),
+            Item::Atom(Atom::Char(v, _)) => visitor.visit_char(*v),
+            Item::Atom(Atom::Float(v, _, _)) => visitor.visit_f64(*v),
+            Item::Atom(Atom::Integer(v, _, _)) => match (v.as_i64(), v.as_u64()) {
+                (Some(v), _) => visitor.visit_i64(v),
+                (_, Some(v)) => visitor.visit_u64(v),
+                _ => Err(de::Error::custom(format!("integer {} out of range", v))),
+            },
+            Item::Atom(Atom::Keyword(v, _)) | Item::Atom(Atom::Symbol(v, _)) => {
+                visitor.visit_borrowed_str(v)
+            }
+            Item::Atom(Atom::Nil(_)) => visitor.visit_unit(),
+            Item::Atom(Atom::String(v, _, _)) => visitor.visit_borrowed_str(v),
+            // Structs are written as property lists, which types buffering their input, like
+            // untagged and internally tagged enums, must see as maps.
+            Item::List(items, _) if is_property_list(&values(items)) => {
+                return self.visit_map(values(items), visitor);
+            }
+            Item::List(items, _) | Item::Set(items, _) | Item::Vector(items, _) => {
+                return self.visit_seq(values(items), visitor);
+            }
+            Item::Map(items, _) => return self.visit_map(values(items), visitor),
+            _ => return self.invalid_type(&visitor),
+        };
+
+        self.spanned(value)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.item {
+            Item::Atom(Atom::Nil(_)) => self.spanned(visitor.visit_none()),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.item {
+            Item::Atom(Atom::Nil(_)) => self.spanned(visitor.visit_unit()),
+            _ => self.invalid_type(&visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.item {
+            Item::List(items, _) | Item::Set(items, _) | Item::Vector(items, _) => {
+                self.visit_seq(values(items), visitor)
+            }
+            _ => self.invalid_type(&visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.item {
+            Item::List(items, _) | Item::Map(items, _) => self.visit_map(values(items), visitor),
+            _ => self.invalid_type(&visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let access = match self.item {
+            Item::Atom(Atom::Symbol(..)) => EnumAccess {
+                variant: self.item,
+                values: vec![],
+                item: self.item,
+            },
+            Item::List(items, _) => {
+                let mut values = values(items);
+
+                match values.first() {
+                    Some(Item::Atom(Atom::Symbol(..))) => EnumAccess {
+                        variant: values.remove(0),
+                        values,
+                        item: self.item,
+                    },
+                    _ => return self.invalid_type(&"a list starting with a symbol"),
+                }
+            }
+            _ => return self.invalid_type(&visitor),
+        };
+
+        self.spanned(visitor.visit_enum(access))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        identifier ignored_any
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        Error::Message(msg.to_string(), Span::default())
+    }
+}
+
+//
+// Private
+//
+
+/// Returns the items that are not comments.
+fn values(items: &[Item]) -> Vec<&Item> {
+    items.iter().filter(|item| !item.is_comment()).collect()
+}
+
+/// Returns `true` if the values alternate keywords and values, like the ones of a struct.
+fn is_property_list(values: &[&Item]) -> bool {
+    !values.is_empty()
+        && values.len().is_multiple_of(2)
+        && values
+            .iter()
+            .step_by(2)
+            .all(|value| matches!(value, Item::Atom(Atom::Keyword(..))))
+}
+
+/// Deserializes an item with the given seed. Errors raised without a span after the deserializer
+/// returns, for instance by types buffering their input, get the span of the item.
+fn deserialize_seed<'de, T>(seed: T, item: &'de Item) -> Result<T::Value>
+where
+    T: DeserializeSeed<'de>,
+{
+    let value = seed.deserialize(Deserializer::new(item));
+
+    Deserializer::new(item).spanned(value)
+}
+
+/// Gives access to the values of a list, vector or set.
+struct SeqAccess<'de> {
+    iter: vec::IntoIter<&'de Item>,
+    count: usize,
+}
+
+impl<'de> SeqAccess<'de> {
+    fn new(values: Vec<&'de Item>) -> Self {
+        Self {
+            iter: values.into_iter(),
+            count: 0,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(item) => {
+                self.count += 1;
+                deserialize_seed(seed, item).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Gives access to the keys and values of a map or of a property list.
+struct MapAccess<'de> {
+    iter: vec::IntoIter<&'de Item>,
+}
+
+impl<'de> MapAccess<'de> {
+    fn new(values: Vec<&'de Item>) -> Self {
+        Self {
+            iter: values.into_iter(),
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(item) => deserialize_seed(seed, item).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let item = self.iter.next().expect("value following a key");
+
+        deserialize_seed(seed, item)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len() / 2)
+    }
+}
+
+/// Gives access to a variant, written as a symbol or as a list starting with a symbol.
+struct EnumAccess<'de> {
+    variant: &'de Item,
+    values: Vec<&'de Item>,
+    /// The whole item, for error reporting.
+    item: &'de Item,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = deserialize_seed(seed, self.variant)?;
+
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.values.len() {
+            0 => Ok(()),
+            len => Err(de::Error::invalid_length(len, &"a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.values.as_slice() {
+            [value] => deserialize_seed(seed, value),
+            values => Err(de::Error::invalid_length(
+                values.len(),
+                &"a newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Deserializer::new(self.item).visit_seq(self.values, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Deserializer::new(self.item).visit_map(self.values, visitor)
+    }
+}
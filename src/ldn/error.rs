@@ -13,7 +13,9 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::error;
 use std::fmt;
+use std::io;
 use std::result;
 
 use super::pos::{Position, Span};
@@ -22,35 +24,107 @@ use super::pos::{Position, Span};
 pub type Result<T> = result::Result<T, Error>;
 
 /// The error type for LDN parsing operations.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Error {
+    CharParseError(String, Span),
+    DatumCommentWithoutValue(Span),
+    DuplicateElement(Span),
+    DuplicateKey(Span),
+    FloatParseError(String, Span),
     IntegerLeadingZero(String, Span),
+    IntegerMisplacedSeparator(String, Span),
+    IntegerMissingDigits(String, Span),
     IntegerParseError(String, Span),
-    InvalidCharacter(u8, Position),
+    InvalidCharacter(char, Position),
+    InvalidEscape(String, Span),
+    /// A tag handler rejected the tagged value. Holds the tag, the handler's message and the span
+    /// of the tag.
+    InvalidTaggedValue(String, String, Span),
+    Io(io::ErrorKind, String, Position),
+    MapOddEntries(Span),
+    MismatchedDelimiter(char, Position),
+    QuoteWithoutValue(Span),
+    RatioParseError(String, Span),
+    RatioZeroDenominator(String, Span),
     SymbolParseError(String, Span),
+    TagWithoutValue(Span),
     UnbalancedParentheses(Position),
+    UnterminatedBlockComment(Span),
+    UnterminatedString(Span),
     Utf8Error(Span),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Error::CharParseError(token, span) => {
+                write!(f, "{} cannot parse '{}' as character", span, token)
+            }
+            Error::DatumCommentWithoutValue(span) => {
+                write!(f, "{} datum comment without a value", span)
+            }
+            Error::DuplicateElement(span) => write!(f, "{} duplicate set element", span),
+            Error::DuplicateKey(span) => write!(f, "{} duplicate map key", span),
+            Error::FloatParseError(token, span) => {
+                write!(
+                    f,
+                    "{} cannot parse '{}' as floating-point number",
+                    span, token
+                )
+            }
             Error::IntegerLeadingZero(token, span) => write!(
                 f,
                 "{} found leading zero while parsing integer constant '{}'",
                 span, token
             ),
+            Error::IntegerMisplacedSeparator(token, span) => write!(
+                f,
+                "{} digit separators must be between digits in integer constant '{}'",
+                span, token
+            ),
+            Error::IntegerMissingDigits(token, span) => {
+                write!(f, "{} missing digits in integer constant '{}'", span, token)
+            }
             Error::IntegerParseError(token, span) => {
                 write!(f, "{} cannot parse '{}' as integer", span, token)
             }
-            Error::InvalidCharacter(ch, pos) => write!(f, "{} invalid character '{}'", pos, ch),
+            Error::InvalidCharacter(ch, pos) => {
+                write!(f, "{} invalid character '{}'", pos, ch.escape_debug())
+            }
+            Error::InvalidEscape(sequence, span) => {
+                write!(f, "{} invalid escape sequence '{}'", span, sequence)
+            }
+            Error::InvalidTaggedValue(tag, message, span) => {
+                write!(f, "{} invalid value for tag '#{}': {}", span, tag, message)
+            }
+            Error::Io(_, message, pos) => write!(f, "{} i/o error: {}", pos, message),
+            Error::MapOddEntries(span) => {
+                write!(f, "{} map has a key without a value", span)
+            }
+            Error::MismatchedDelimiter(ch, pos) => {
+                write!(f, "{} mismatched closing delimiter '{}'", pos, ch)
+            }
+            Error::QuoteWithoutValue(span) => write!(f, "{} quote without a value", span),
+            Error::RatioParseError(token, span) => {
+                write!(f, "{} cannot parse '{}' as ratio", span, token)
+            }
+            Error::RatioZeroDenominator(token, span) => {
+                write!(f, "{} zero denominator in ratio '{}'", span, token)
+            }
             Error::SymbolParseError(token, span) => {
                 write!(f, "{} cannot parse '{}' as symbol", span, token)
             }
+            Error::TagWithoutValue(span) => write!(f, "{} tag without a value", span),
             Error::UnbalancedParentheses(pos) => {
                 write!(f, "{} unbalanced parentheses in list`", pos)
             }
+            Error::UnterminatedBlockComment(span) => {
+                write!(f, "{} unterminated block comment", span)
+            }
+            Error::UnterminatedString(span) => write!(f, "{} unterminated string", span),
             Error::Utf8Error(s) => write!(f, "{} utf-8 decode error", s),
         }
     }
 }
+
+impl error::Error for Error {}
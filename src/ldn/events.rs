@@ -0,0 +1,201 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::ast::{Atom, Quote};
+use super::error::Result;
+use super::parser::Parser;
+use super::pos::Span;
+
+/// An event emitted by the streaming parser.
+///
+/// Lists are reported as a `StartList` event, followed by the events of their items and by a
+/// matching `EndList` event. Vectors, maps and sets are reported in the same way. Since events
+/// don't retain items, repeated map keys and set elements are only detected when building items.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// An opening paren. The span covers the paren alone.
+    StartList(Span),
+    /// A closing paren. The span covers the paren alone.
+    EndList(Span),
+    /// An opening square bracket.
+    StartVector(Span),
+    /// A closing square bracket.
+    EndVector(Span),
+    /// An opening brace.
+    StartMap(Span),
+    /// A closing brace ending a map.
+    EndMap(Span),
+    /// An opening hash and brace. The span covers both characters.
+    StartSet(Span),
+    /// A closing brace ending a set.
+    EndSet(Span),
+    /// A tag, followed by the events of the tagged value. The span covers the hash and the tag.
+    Tag(String, Span),
+    /// A quote shorthand, followed by the events of the quoted item. The span covers the shorthand
+    /// alone.
+    Quote(Quote, Span),
+    Atom(Atom),
+    Comment(String, Span),
+    /// A block comment. The string holds the text between the outermost delimiters, nested
+    /// block comments included.
+    BlockComment(String, Span),
+    /// A datum comment, followed by the events of the commented datum. The span covers the hash
+    /// and the underscore.
+    DatumComment(Span),
+}
+
+impl Event {
+    /// Returns the `Span` associated with the event.
+    pub fn span(&self) -> &Span {
+        match self {
+            Event::StartList(span) => span,
+            Event::EndList(span) => span,
+            Event::StartVector(span) => span,
+            Event::EndVector(span) => span,
+            Event::StartMap(span) => span,
+            Event::EndMap(span) => span,
+            Event::StartSet(span) => span,
+            Event::EndSet(span) => span,
+            Event::Tag(_, span) => span,
+            Event::Quote(_, span) => span,
+            Event::Atom(atom) => atom.span(),
+            Event::Comment(_, span) => span,
+            Event::BlockComment(_, span) => span,
+            Event::DatumComment(span) => span,
+        }
+    }
+}
+
+/// An iterator over the parsing events of a byte stream, created by `Parser::events()`.
+pub struct Events<'a, I>
+where
+    I: Iterator<Item = u8>,
+{
+    parser: &'a mut Parser<I>,
+    done: bool,
+}
+
+impl<'a, I> Events<'a, I>
+where
+    I: Iterator<Item = u8>,
+{
+    pub(crate) fn new(parser: &'a mut Parser<I>) -> Self {
+        Self {
+            parser,
+            done: false,
+        }
+    }
+}
+
+impl<'a, I> Iterator for Events<'a, I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let event = self.parser.next_event();
+
+        if let None | Some(Err(_)) = event {
+            self.done = true;
+        }
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::ast::StringStyle;
+    use super::super::error::Error;
+    use super::super::integer::Integer;
+    use super::super::options::Options;
+    use super::super::pos::{pos, span};
+
+    #[test]
+    fn events() {
+        assert_eq!(
+            vec![
+                Ok(Event::Comment("foo".into(), span(0, 0, 0, 5))),
+                Ok(Event::StartList(span(1, 0, 1, 1))),
+                Ok(Event::Atom(Atom::Symbol("a".into(), span(1, 1, 1, 2)))),
+                Ok(Event::StartList(span(1, 3, 1, 4))),
+                Ok(Event::EndList(span(1, 4, 1, 5))),
+                Ok(Event::Atom(Atom::String(
+                    "b".into(),
+                    StringStyle::Plain,
+                    span(1, 6, 1, 9)
+                ))),
+                Ok(Event::EndList(span(1, 9, 1, 10))),
+                Ok(Event::Atom(Atom::Integer(
+                    Integer::from(1),
+                    None,
+                    span(1, 11, 1, 12)
+                ))),
+            ],
+            Parser::from("; foo\n(a () \"b\") 1")
+                .events()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn events_collections() {
+        assert_eq!(
+            vec![
+                Ok(Event::StartVector(span(0, 0, 0, 1))),
+                Ok(Event::StartMap(span(0, 1, 0, 2))),
+                Ok(Event::EndMap(span(0, 2, 0, 3))),
+                Ok(Event::StartSet(span(0, 4, 0, 6))),
+                Ok(Event::EndSet(span(0, 6, 0, 7))),
+                Ok(Event::EndVector(span(0, 7, 0, 8))),
+                Ok(Event::Tag("a".into(), span(0, 9, 0, 11))),
+                Ok(Event::Atom(Atom::Keyword("b".into(), span(0, 12, 0, 14)))),
+                Ok(Event::Quote(Quote::UnquoteSplicing, span(0, 15, 0, 17))),
+                Ok(Event::Atom(Atom::Symbol("c".into(), span(0, 17, 0, 18)))),
+            ],
+            Parser::from("[{} #{}] #a :b ,@c")
+                .with_options(Options::extended())
+                .events()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn events_error() {
+        assert_eq!(
+            vec![
+                Ok(Event::StartList(span(0, 0, 0, 1))),
+                Ok(Event::Atom(Atom::Symbol("a".into(), span(0, 1, 0, 2)))),
+                Err(Error::UnbalancedParentheses(pos(0, 2))),
+            ],
+            Parser::from("(a").events().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            vec![Err(Error::IntegerLeadingZero(
+                "01".into(),
+                span(0, 0, 0, 2)
+            ))],
+            Parser::from("01 a").events().collect::<Vec<_>>()
+        );
+    }
+}
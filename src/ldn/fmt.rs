@@ -13,27 +13,55 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::ast::{Atom, Item};
+use super::ast::{Atom, Item, StringStyle};
+use super::integer::Integer;
+use super::parser::{parse_float, parse_integer};
+use super::pos::Span;
 
 const INDENT_LEVEL: usize = 4;
 
 /// Formats (pretty-prints) an LDN document.
 pub fn fmt(top: &[Item]) -> String {
-    fmt_items(top, true, 0)
+    fmt_items(top, None, 0)
+}
+
+/// Formats an item on a single line, separating items with a space. Line comments and malformed
+/// input spanning multiple lines are the only things that can break the line.
+pub(crate) fn fmt_inline(item: &Item) -> String {
+    match item {
+        Item::Atom(Atom::String(string, StringStyle::Raw(hashes), _))
+            if is_raw(string) && !string.contains('\n') =>
+        {
+            fmt_raw_string(string, *hashes)
+        }
+        Item::Atom(Atom::String(string, _, _)) => fmt_string(string),
+        Item::Atom(atom) => fmt_atom(atom, 0),
+        Item::DatumComment(value, _) => format!("#_{}", fmt_inline(value)),
+        Item::List(items, _) => fmt_inline_items(items, "(", ")"),
+        Item::Map(items, _) => fmt_inline_items(items, "{", "}"),
+        Item::Quoted(quote, value, _) => format!("{}{}", quote.prefix(), fmt_inline(value)),
+        Item::Set(items, _) => fmt_inline_items(items, "#{", "}"),
+        Item::Tagged(tag, value, _) => format!("#{} {}", tag, fmt_inline(value)),
+        Item::Vector(items, _) => fmt_inline_items(items, "[", "]"),
+        Item::BlockComment(_, _) | Item::Comment(_, _) | Item::Error(_, _) => fmt_item(item, 0),
+    }
 }
 
 //
 // Private
 //
 
-fn fmt_items(items: &[Item], is_top_level: bool, lhs: usize) -> String {
+/// Formats a sequence of items, surrounded by the given opening and closing delimiters unless
+/// they are top-level items.
+fn fmt_items(items: &[Item], delimiters: Option<(&str, &str)>, lhs: usize) -> String {
     // TODO(lvillani): Can we rewrite this using iterators?
 
+    let is_top_level = delimiters.is_none();
     let mut prev: Option<&Item> = None;
     let mut ret = String::new();
 
-    if !is_top_level {
-        ret += "(";
+    if let Some((opening, _)) = delimiters {
+        ret += opening;
     }
 
     for item in items {
@@ -42,7 +70,7 @@ fn fmt_items(items: &[Item], is_top_level: bool, lhs: usize) -> String {
                 // Always insert an empty line between consecutive non-comment items at top-level.
                 2
             } else {
-                item.span().start.line - prev.span().start.line
+                item.span().start.line - prev.span().end.line
             };
 
             if delta == 0 {
@@ -63,28 +91,216 @@ fn fmt_items(items: &[Item], is_top_level: bool, lhs: usize) -> String {
         prev = Some(item);
     }
 
-    if !is_top_level {
-        ret += ")";
-    } else {
-        ret += "\n";
+    match delimiters {
+        // A line comment runs up to the end of the line, so it can't be followed by the closing
+        // delimiter.
+        Some((_, closing)) if matches!(prev, Some(Item::Comment(_, _))) => {
+            ret += "\n";
+            ret += &" ".repeat(lhs - INDENT_LEVEL);
+            ret += closing;
+        }
+        Some((_, closing)) => ret += closing,
+        None => ret += "\n",
+    }
+
+    ret
+}
+
+/// Formats a sequence of items on a single line, surrounded by the given delimiters. Line
+/// comments are followed by a line break.
+fn fmt_inline_items(items: &[Item], opening: &str, closing: &str) -> String {
+    let mut ret = String::from(opening);
+
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 && !ret.ends_with('\n') {
+            ret += " ";
+        }
+
+        ret += &fmt_inline(item);
+
+        if let Item::Comment(_, _) = item {
+            ret += "\n";
+        }
     }
 
+    ret += closing;
     ret
 }
 
 fn fmt_item(item: &Item, lhs: usize) -> String {
     match item {
-        Item::Atom(atom) => fmt_atom(atom),
+        Item::Atom(atom) => fmt_atom(atom, lhs),
+        Item::BlockComment(comment, _) => format!("#|{}|#", normalize_newlines(comment)),
         Item::Comment(comment, _) => format!("; {}", comment),
-        Item::List(items, _) => fmt_items(items, false, lhs + INDENT_LEVEL),
+        Item::DatumComment(value, _) => format!("#_{}", fmt_item(value, lhs)),
+        Item::Error(text, _) => normalize_newlines(text),
+        Item::List(items, _) => fmt_items(items, Some(("(", ")")), lhs + INDENT_LEVEL),
+        Item::Map(items, _) => fmt_items(items, Some(("{", "}")), lhs + INDENT_LEVEL),
+        Item::Quoted(quote, value, _) => {
+            format!("{}{}", quote.prefix(), fmt_item(value, lhs))
+        }
+        Item::Set(items, _) => fmt_items(items, Some(("#{", "}")), lhs + INDENT_LEVEL),
+        Item::Tagged(tag, value, _) => format!("#{} {}", tag, fmt_item(value, lhs)),
+        Item::Vector(items, _) => fmt_items(items, Some(("[", "]")), lhs + INDENT_LEVEL),
     }
 }
 
-fn fmt_atom(atom: &Atom) -> String {
+fn fmt_atom(atom: &Atom, lhs: usize) -> String {
     match atom {
-        Atom::Integer(integer, _) => integer.to_string(),
+        Atom::Bool(v, _) => v.to_string(),
+        Atom::Char(ch, _) => fmt_char(*ch),
+        Atom::Float(float, literal, _) => fmt_float(*float, literal.as_deref()),
+        Atom::Integer(integer, literal, _) => fmt_integer(integer, literal.as_deref()),
         Atom::Keyword(keyword, _) => format!(":{}", keyword),
-        Atom::String(string, _) => format!(r#""{}""#, string),
+        Atom::Nil(_) => "nil".into(),
+        Atom::Ratio(numerator, denominator, _) => format!("{}/{}", numerator, denominator),
+        Atom::String(string, StringStyle::Raw(hashes), _) if is_raw(string) => {
+            fmt_raw_string(string, *hashes)
+        }
+        Atom::String(string, StringStyle::Block, _) => fmt_text_block(string, lhs),
+        Atom::String(string, _, _) => fmt_string(string),
         Atom::Symbol(symbol, _) => symbol.clone(),
     }
 }
+
+/// Formats an integer the way it was written, unless the literal doesn't denote its value anymore.
+fn fmt_integer(integer: &Integer, literal: Option<&str>) -> String {
+    let denotes = |literal: &str| match parse_integer(literal.into(), Span::default()) {
+        Ok(Atom::Integer(v, _, _)) => v == *integer,
+        _ => false,
+    };
+
+    match literal {
+        Some(literal) if denotes(literal) => literal.into(),
+        _ => integer.to_string(),
+    }
+}
+
+/// Formats a floating-point number the way it was written, unless the literal doesn't denote its
+/// value anymore.
+fn fmt_float(float: f64, literal: Option<&str>) -> String {
+    let denotes = |literal: &str| match parse_float(literal.into(), Span::default()) {
+        Ok(Atom::Float(v, _, _)) => v == float,
+        _ => false,
+    };
+
+    match literal {
+        Some(literal) if denotes(literal) => literal.into(),
+        // The debug representation is the shortest one that parses back to the same value, and
+        // always has either a decimal point or an exponent.
+        _ => format!("{:?}", float),
+    }
+}
+
+/// Formats a character literal, using names for whitespace and escapes for control characters.
+fn fmt_char(ch: char) -> String {
+    match ch {
+        '\n' => r"\newline".into(),
+        '\r' => r"\return".into(),
+        ' ' => r"\space".into(),
+        '\t' => r"\tab".into(),
+        _ if !ch.is_control() => format!("\\{}", ch),
+        _ => format!(r"\u{{{:x}}}", ch as u32),
+    }
+}
+
+/// Replaces CRLF sequences and lone carriage returns with line feeds.
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Formats a string, escaping characters so that the result parses back into the same string.
+fn fmt_string(string: &str) -> String {
+    let mut ret = String::with_capacity(string.len() + 2);
+
+    ret.push('"');
+
+    for ch in string.chars() {
+        match ch {
+            '"' => ret.push_str(r#"\""#),
+            '\\' => ret.push_str(r"\\"),
+            '\n' => ret.push_str(r"\n"),
+            '\r' => ret.push_str(r"\r"),
+            '\t' => ret.push_str(r"\t"),
+            _ if !ch.is_control() => ret.push(ch),
+            _ => ret.push_str(&format!(r"\u{{{:x}}}", ch as u32)),
+        }
+    }
+
+    ret.push('"');
+    ret
+}
+
+/// Returns `true` if `string` can be written as a raw string, which has no escape sequences.
+fn is_raw(string: &str) -> bool {
+    string
+        .chars()
+        .all(|ch| !ch.is_control() || matches!(ch, '\t' | '\n' | '\x0c'))
+}
+
+/// Formats a raw string, adding hashes to the delimiters if needed so that the string doesn't
+/// contain the closing one.
+fn fmt_raw_string(string: &str, hashes: usize) -> String {
+    let mut hashes = "#".repeat(hashes.max(1));
+
+    while string.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+
+    format!("{}\"{}\"{}", hashes, string, hashes)
+}
+
+/// Formats a text block with its lines indented by `lhs` columns, escaping characters so that
+/// the result parses back into the same string.
+fn fmt_text_block(string: &str, lhs: usize) -> String {
+    let is_space = |ch: char| ch == ' ' || ch == '\t';
+    let lines = string.split('\n').collect::<Vec<_>>();
+    let last = lines.len() - 1;
+
+    // The shared indentation is stripped when parsing, so at least one line must start right at
+    // the margin. This is always the case for trailing whitespace, which is escaped, and for the
+    // closing delimiter on its own line.
+    let at_margin = |(i, line): (usize, &&str)| match line.chars().next() {
+        None => i == last,
+        Some(ch) => !is_space(ch) || line.trim_end_matches(is_space).is_empty(),
+    };
+    let escape_indentation = !lines.iter().enumerate().any(at_margin);
+    let first = lines.iter().position(|line| !line.is_empty());
+
+    let mut ret = String::from("\"\"\"");
+
+    for (i, line) in lines.iter().enumerate() {
+        ret.push('\n');
+
+        if !line.is_empty() || i == last {
+            ret += &" ".repeat(lhs);
+        }
+
+        let end = line.trim_end_matches(is_space).len();
+        let mut quotes = 0;
+
+        for (j, ch) in line.char_indices() {
+            let escape_space = j >= end || (escape_indentation && first == Some(i) && j == 0);
+
+            // Every third quotation mark in a row is escaped, and so is the last one of the
+            // string, so that they don't close the text block.
+            let escape_quote = ch == '"' && (quotes % 3 == 2 || (i == last && j + 1 == line.len()));
+
+            quotes = if ch == '"' { quotes + 1 } else { 0 };
+
+            match ch {
+                ' ' if escape_space => ret.push_str(r"\u{20}"),
+                '\t' if escape_space => ret.push_str(r"\t"),
+                '"' if escape_quote => ret.push_str(r#"\""#),
+                '\\' => ret.push_str(r"\\"),
+                '\r' => ret.push_str(r"\r"),
+                '\t' => ret.push(ch),
+                _ if !ch.is_control() => ret.push(ch),
+                _ => ret.push_str(&format!(r"\u{{{:x}}}", ch as u32)),
+            }
+        }
+    }
+
+    ret += "\"\"\"";
+    ret
+}
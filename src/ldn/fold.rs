@@ -0,0 +1,180 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Rewriting of owned items.
+
+use super::ast::{Atom, Item, List};
+
+/// Rewrites items bottom-up, taking them by value.
+///
+/// By default every method returns its argument with its children folded, so implementations
+/// only need to override the methods for what they rewrite. Overriding `fold_list` allows
+/// removing items or replacing one item with many, while `fold_item` can call `fold_children` to
+/// rewrite an item after or instead of its children.
+pub trait Fold {
+    /// Folds an item.
+    fn fold_item(&mut self, item: Item) -> Item {
+        fold_children(self, item)
+    }
+
+    /// Folds the atom of an atom item.
+    fn fold_atom(&mut self, atom: Atom) -> Atom {
+        atom
+    }
+
+    /// Folds the items of a list, vector, map or set, and top-level items.
+    fn fold_list(&mut self, items: List) -> List {
+        fold_items(self, items)
+    }
+}
+
+/// Folds a sequence of top-level items.
+pub fn fold<F>(folder: &mut F, items: List) -> List
+where
+    F: Fold + ?Sized,
+{
+    folder.fold_list(items)
+}
+
+/// Folds each of the given items.
+pub fn fold_items<F>(folder: &mut F, items: List) -> List
+where
+    F: Fold + ?Sized,
+{
+    items
+        .into_iter()
+        .map(|item| folder.fold_item(item))
+        .collect()
+}
+
+/// Folds the children of an item, keeping its span.
+pub fn fold_children<F>(folder: &mut F, item: Item) -> Item
+where
+    F: Fold + ?Sized,
+{
+    match item {
+        Item::Atom(atom) => Item::Atom(folder.fold_atom(atom)),
+        Item::BlockComment(_, _) | Item::Comment(_, _) | Item::Error(_, _) => item,
+        Item::DatumComment(value, span) => {
+            Item::DatumComment(Box::new(folder.fold_item(*value)), span)
+        }
+        Item::List(items, span) => Item::List(folder.fold_list(items), span),
+        Item::Map(items, span) => Item::Map(folder.fold_list(items), span),
+        Item::Quoted(quote, value, span) => {
+            Item::Quoted(quote, Box::new(folder.fold_item(*value)), span)
+        }
+        Item::Set(items, span) => Item::Set(folder.fold_list(items), span),
+        Item::Tagged(tag, value, span) => {
+            Item::Tagged(tag, Box::new(folder.fold_item(*value)), span)
+        }
+        Item::Vector(items, span) => Item::Vector(folder.fold_list(items), span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::integer::Integer;
+    use super::super::parser::Parser;
+    use super::super::pos::span;
+
+    #[test]
+    fn fold_atoms() {
+        /// Renames a symbol and increments integers.
+        struct Rewrite;
+
+        impl Fold for Rewrite {
+            fn fold_atom(&mut self, atom: Atom) -> Atom {
+                match atom {
+                    Atom::Integer(v, _, span) => {
+                        Atom::Integer(Integer::from(v.as_i64().unwrap() + 1), None, span)
+                    }
+                    Atom::Symbol(v, span) if v == "old" => Atom::Symbol("new".into(), span),
+                    _ => atom,
+                }
+            }
+        }
+
+        let items = Parser::from("(old 0x1 (old #_2))").parse().unwrap();
+
+        assert_eq!(
+            vec![Item::List(
+                vec![
+                    Item::Atom(Atom::Symbol("new".into(), span(0, 1, 0, 4))),
+                    Item::Atom(Atom::Integer(Integer::from(2), None, span(0, 5, 0, 8))),
+                    Item::List(
+                        vec![
+                            Item::Atom(Atom::Symbol("new".into(), span(0, 10, 0, 13))),
+                            Item::DatumComment(
+                                Box::new(Item::Atom(Atom::Integer(
+                                    Integer::from(3),
+                                    None,
+                                    span(0, 16, 0, 17)
+                                ))),
+                                span(0, 14, 0, 17)
+                            )
+                        ],
+                        span(0, 9, 0, 18)
+                    )
+                ],
+                span(0, 0, 0, 19)
+            )],
+            fold(&mut Rewrite, items)
+        );
+    }
+
+    #[test]
+    fn fold_lists() {
+        /// Removes comments and splices `(splice ...)` lists into their parent.
+        struct Splice;
+
+        impl Fold for Splice {
+            fn fold_list(&mut self, items: List) -> List {
+                let mut ret = vec![];
+
+                for item in fold_items(self, items) {
+                    match item {
+                        Item::List(mut items, _)
+                            if items.first().and_then(|item| match item {
+                                Item::Atom(atom) => atom.name(),
+                                _ => None,
+                            }) == Some("splice") =>
+                        {
+                            ret.extend(items.drain(1..))
+                        }
+                        _ if item.is_comment() => {}
+                        _ => ret.push(item),
+                    }
+                }
+
+                ret
+            }
+        }
+
+        let items = Parser::from("(a ; b\n (splice c (splice d)) e) (splice)")
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            "(a c d e)",
+            fold(&mut Splice, items)
+                .iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+}
@@ -0,0 +1,751 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Incremental reparsing of edited documents.
+//!
+//! A `Document` keeps the lossless syntax tree of a text split in top-level elements, along with
+//! the items and errors derived from each of them. When the text is edited only the top-level
+//! elements touched by the edit are re-tokenized and re-parsed, while the ones following it are
+//! simply moved to their new position.
+
+use std::sync::Arc;
+
+use super::ast::{Item, List};
+use super::cst::{self, GreenElement, GreenNode, SyntaxKind, SyntaxNode};
+use super::error::Error;
+use super::fold::{fold_children, Fold};
+use super::options::Options;
+use super::parser::{is_closing, is_newline, is_whitespace};
+use super::pos::{Position, Span};
+
+/// A text document that can be reparsed incrementally.
+pub struct Document {
+    text: String,
+    options: Options,
+    /// Byte offset at which each line of the text begins.
+    line_starts: Vec<usize>,
+    children: Vec<Child>,
+}
+
+/// A top-level element of the document along with the results of parsing it.
+struct Child {
+    green: GreenElement,
+    offset: usize,
+    start: Position,
+    /// Position at which `items` and `errors` were derived. They are relocated on access when the
+    /// child has been moved since then.
+    origin: Position,
+    items: List,
+    errors: Vec<Error>,
+}
+
+impl Document {
+    /// Creates a new strict LDN document, parsing the given text from scratch.
+    pub fn new(text: String) -> Self {
+        Self::with_options(text, Options::default())
+    }
+
+    /// Creates a new document in the dialect selected by `options`, parsing the given text from
+    /// scratch. Like in the syntax tree, tag handlers aren't run and tabs count as a single
+    /// column.
+    pub fn with_options(text: String, options: Options) -> Self {
+        let children = parse_children(&text, 0, Position::default(), &options);
+        let mut line_starts = vec![0];
+        line_starts.extend(find_line_starts(&text, 0, text.len()));
+
+        Self {
+            text,
+            options,
+            line_starts,
+            children,
+        }
+    }
+
+    /// Returns the current text of the document.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text in `range` with `new_text` and reparses the affected top-level elements.
+    pub fn edit(&mut self, range: &Span, new_text: &str) {
+        let edit_start = self.offset_of(&range.start);
+        let edit_end = self.offset_of(&range.end).max(edit_start);
+
+        self.text.replace_range(edit_start..edit_end, new_text);
+        self.update_line_starts(edit_start, edit_end, new_text.len());
+
+        if self.children.is_empty() {
+            self.children = parse_children(&self.text, 0, Position::default(), &self.options);
+            return;
+        }
+
+        let delta = new_text.len() as isize - (edit_end - edit_start) as isize;
+
+        // The first affected element is the one containing the byte right before the edit: since
+        // its first byte didn't change, whatever precedes it is still tokenized the same way.
+        let first = self
+            .children
+            .iter()
+            .rposition(|child| child.offset < edit_start)
+            .unwrap_or(0);
+
+        // The last affected element is the first one ending after the edit, so that its last byte
+        // didn't change either.
+        let mut last = self
+            .children
+            .iter()
+            .position(|child| child.offset + child.green.len() > edit_end)
+            .unwrap_or(self.children.len() - 1);
+
+        let region_offset = self.children[first].offset;
+        let region_start = self.children[first].start.clone();
+
+        let mut reparsed = loop {
+            let region_end = shift(
+                self.children[last].offset + self.children[last].green.len(),
+                delta,
+            );
+            let reparsed = parse_children(
+                &self.text[region_offset..region_end],
+                region_offset,
+                region_start.clone(),
+                &self.options,
+            );
+
+            // If the edited region ends in the middle of a list or string the rest of the document
+            // is affected as well.
+            let next = self.text.as_bytes().get(region_end).cloned();
+
+            if last == self.children.len() - 1 || is_complete(&reparsed, next, &self.options) {
+                break reparsed;
+            }
+
+            last = self.children.len() - 1;
+        };
+
+        // Move the following elements to their new location.
+        if let Some(next) = self.children.get(last + 1) {
+            let old_end = next.start.clone();
+            let new_end = reparsed
+                .last()
+                .map_or(region_start, |child| end_of(&child.green, &child.start));
+
+            for child in &mut self.children[last + 1..] {
+                child.offset = shift(child.offset, delta);
+                child.start = relocate(&child.start, &old_end, &new_end);
+            }
+        }
+
+        self.children
+            .splice(first..=last, reparsed.drain(..))
+            .for_each(drop);
+    }
+
+    /// Returns the lossless syntax tree of the document.
+    pub fn syntax(&self) -> SyntaxNode {
+        let children = self.children.iter().map(|c| c.green.clone()).collect();
+
+        SyntaxNode::new_root(Arc::new(GreenNode::new(SyntaxKind::Root, children)))
+            .with_options(self.options.clone())
+    }
+
+    /// Returns the top-level items of the document, with malformed input kept as `Item::Error`.
+    pub fn items(&self) -> List {
+        self.children
+            .iter()
+            .flat_map(|child| {
+                child
+                    .items
+                    .iter()
+                    .map(move |item| relocate_item(item, &child.origin, &child.start))
+            })
+            .collect()
+    }
+
+    /// Returns every error found in the document.
+    pub fn errors(&self) -> Vec<Error> {
+        self.children
+            .iter()
+            .flat_map(|child| {
+                child
+                    .errors
+                    .iter()
+                    .map(move |err| relocate_error(err, &child.origin, &child.start))
+            })
+            .collect()
+    }
+
+    //
+    // Private
+    //
+
+    /// Converts a position to a byte offset in the current text, clamping it to the end of its
+    /// line or of the document.
+    fn offset_of(&self, pos: &Position) -> usize {
+        let line_start = match self.line_starts.get(pos.line) {
+            Some(&line_start) => line_start,
+            None => return self.text.len(),
+        };
+
+        // Columns are counted in characters.
+        self.text[line_start..]
+            .char_indices()
+            .enumerate()
+            .find(|&(column, (_, ch))| column == pos.column || is_newline(ch))
+            .map_or(self.text.len(), |(_, (index, _))| line_start + index)
+    }
+
+    /// Updates the line starts after replacing the text between `edit_start` and `edit_end` with
+    /// `new_len` bytes. Only the lines touched by the edit are scanned again.
+    fn update_line_starts(&mut self, edit_start: usize, edit_end: usize, new_len: usize) {
+        // A line break right before the edit can be merged with the new text into a CRLF
+        // sequence, so the line preceding it is scanned as well.
+        let first = self
+            .line_starts
+            .partition_point(|&start| start < edit_start)
+            .saturating_sub(1);
+
+        // Lines starting after the byte following the edit are preceded by the same line break.
+        let last = self
+            .line_starts
+            .partition_point(|&start| start <= edit_end + 1);
+
+        let delta = new_len as isize - (edit_end - edit_start) as isize;
+        let new_starts = find_line_starts(
+            &self.text,
+            self.line_starts[first],
+            edit_start + new_len + 1,
+        );
+
+        for start in &mut self.line_starts[last..] {
+            *start = shift(*start, delta);
+        }
+
+        self.line_starts.splice(first + 1..last, new_starts);
+    }
+}
+
+//
+// Private
+//
+
+/// Parses `text`, which begins at the given `offset` and `start` position of a document, into a
+/// list of top-level elements.
+fn parse_children(text: &str, offset: usize, start: Position, options: &Options) -> Vec<Child> {
+    let mut offset = offset;
+    let mut start = start;
+
+    cst::parse_with_options(text, options.clone())
+        .green()
+        .children()
+        .iter()
+        .map(|green| {
+            let root = Arc::new(GreenNode::new(SyntaxKind::Root, vec![green.clone()]));
+            let (items, errors) = SyntaxNode::new_root_at(root, offset, start.clone())
+                .with_options(options.clone())
+                .to_items_recovering();
+
+            let child = Child {
+                green: green.clone(),
+                offset,
+                start: start.clone(),
+                origin: start.clone(),
+                items,
+                errors,
+            };
+
+            offset += green.len();
+            start = end_of(green, &start);
+
+            child
+        })
+        .collect()
+}
+
+/// Returns the offsets at which lines begin in `text`, after `from` and up to `until`. A CRLF
+/// sequence is a single line break.
+fn find_line_starts(text: &str, from: usize, until: usize) -> Vec<usize> {
+    let bytes = text.as_bytes();
+    let mut ret = vec![];
+    let mut i = from;
+
+    while i < bytes.len() && i < until {
+        i += match &bytes[i..] {
+            [b'\r', b'\n', ..] => 2,
+            [b'\r', ..] | [b'\n', ..] => 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        if i <= until {
+            ret.push(i);
+        }
+    }
+
+    ret
+}
+
+/// Returns `true` if the last of the given elements would be tokenized the same way when followed
+/// by the byte `next`, meaning that the elements following them are not affected. This is not the
+/// case for unclosed lists and strings, for instance.
+fn is_complete(children: &[Child], next: Option<u8>, options: &Options) -> bool {
+    match children.last() {
+        None => true,
+        Some(child) => is_complete_element(&child.green, next, options),
+    }
+}
+
+/// Returns `true` if the given element would be tokenized the same way when followed by the byte
+/// `next`.
+fn is_complete_element(green: &GreenElement, next: Option<u8>, options: &Options) -> bool {
+    match green {
+        // Datum comments, quoted items and tagged elements are complete once their value is.
+        GreenElement::Node(node) if node.kind().is_prefixed() => {
+            node.children().len() > 1
+                && node.children().last().is_some_and(|last| {
+                    let kind = last.kind();
+
+                    !kind.is_trivia()
+                        && !kind.is_comment()
+                        && is_complete_element(last, next, options)
+                })
+        }
+        GreenElement::Node(node) => node
+            .children()
+            .last()
+            .is_some_and(|last| Some(last.kind()) == node.kind().closing()),
+        GreenElement::Token(token) => match token.kind() {
+            // Two quotation marks followed by a third one open a text block.
+            SyntaxKind::String if token.text() == "\"\"" => next.is_none_or(|ch| ch != b'"'),
+            SyntaxKind::String => is_terminated_string(token.text()),
+            SyntaxKind::Comment => next.is_none_or(|ch| is_newline(char::from(ch))),
+            SyntaxKind::BlockComment => is_terminated_block_comment(token.text()),
+            SyntaxKind::Whitespace => next.is_none_or(|ch| !is_whitespace(char::from(ch))),
+            // A lone backslash would take a following paren as a character literal.
+            SyntaxKind::Atom if token.text() == "\\" => {
+                next.is_none_or(|ch| is_whitespace(char::from(ch)))
+            }
+            SyntaxKind::Atom => next.is_none_or(|ch| {
+                is_whitespace(char::from(ch)) || is_closing(char::from(ch), options.collections)
+            }),
+            _ => true,
+        },
+    }
+}
+
+/// Returns `true` if the string token ends with a closing, non-escaped, delimiter.
+fn is_terminated_string(text: &str) -> bool {
+    let hashes = text.bytes().take_while(|&ch| ch == b'#').count();
+
+    if hashes > 0 {
+        let closing = format!("\"{}", "#".repeat(hashes));
+
+        return text[hashes + 1..].ends_with(&closing);
+    }
+
+    let delimiter = if text.starts_with("\"\"\"") {
+        "\"\"\""
+    } else {
+        "\""
+    };
+    let bytes = text.as_bytes();
+    let mut i = delimiter.len();
+
+    while i < bytes.len() {
+        if bytes[i..].starts_with(delimiter.as_bytes()) {
+            return i + delimiter.len() == bytes.len();
+        }
+
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+
+    false
+}
+
+/// Returns `true` if the block comment token ends with the delimiter closing the opening one.
+fn is_terminated_block_comment(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i + 1 < bytes.len() {
+        match (bytes[i], bytes[i + 1]) {
+            (b'#', b'|') => depth += 1,
+            (b'|', b'#') => depth -= 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+
+        if depth == 0 {
+            return i + 2 == bytes.len();
+        }
+
+        i += 2;
+    }
+
+    false
+}
+
+/// Returns the position following the given element, which begins at `start`.
+fn end_of(green: &GreenElement, start: &Position) -> Position {
+    let root = Arc::new(GreenNode::new(SyntaxKind::Root, vec![green.clone()]));
+
+    SyntaxNode::new_root_at(root, 0, start.clone()).span().end
+}
+
+/// Adds a signed delta to a byte offset.
+fn shift(offset: usize, delta: isize) -> usize {
+    (offset as isize + delta) as usize
+}
+
+/// Moves `pos`, which follows `from`, so that it keeps the same distance from `to`.
+fn relocate(pos: &Position, from: &Position, to: &Position) -> Position {
+    if pos.line == from.line {
+        Position::new(to.line, to.column + (pos.column - from.column))
+    } else {
+        Position::new(pos.line - from.line + to.line, pos.column)
+    }
+}
+
+fn relocate_span(span: &Span, from: &Position, to: &Position) -> Span {
+    Span::new(
+        relocate(&span.start, from, to),
+        relocate(&span.end, from, to),
+    )
+}
+
+fn relocate_item(item: &Item, from: &Position, to: &Position) -> Item {
+    if from == to {
+        return item.clone();
+    }
+
+    Relocate { from, to }.fold_item(item.clone())
+}
+
+/// Moves the spans of items, which follow `from`, so that they keep the same distance from `to`.
+struct Relocate<'a> {
+    from: &'a Position,
+    to: &'a Position,
+}
+
+impl Fold for Relocate<'_> {
+    fn fold_item(&mut self, item: Item) -> Item {
+        let mut item = fold_children(self, item);
+        let span = relocate_span(item.span(), self.from, self.to);
+
+        *item.span_mut() = span;
+        item
+    }
+}
+
+fn relocate_error(err: &Error, from: &Position, to: &Position) -> Error {
+    match err {
+        Error::CharParseError(v, span) => {
+            Error::CharParseError(v.clone(), relocate_span(span, from, to))
+        }
+        Error::DatumCommentWithoutValue(span) => {
+            Error::DatumCommentWithoutValue(relocate_span(span, from, to))
+        }
+        Error::DuplicateElement(span) => Error::DuplicateElement(relocate_span(span, from, to)),
+        Error::DuplicateKey(span) => Error::DuplicateKey(relocate_span(span, from, to)),
+        Error::FloatParseError(v, span) => {
+            Error::FloatParseError(v.clone(), relocate_span(span, from, to))
+        }
+        Error::InvalidTaggedValue(tag, message, span) => {
+            Error::InvalidTaggedValue(tag.clone(), message.clone(), relocate_span(span, from, to))
+        }
+        Error::IntegerLeadingZero(v, span) => {
+            Error::IntegerLeadingZero(v.clone(), relocate_span(span, from, to))
+        }
+        Error::IntegerMisplacedSeparator(v, span) => {
+            Error::IntegerMisplacedSeparator(v.clone(), relocate_span(span, from, to))
+        }
+        Error::IntegerMissingDigits(v, span) => {
+            Error::IntegerMissingDigits(v.clone(), relocate_span(span, from, to))
+        }
+        Error::IntegerParseError(v, span) => {
+            Error::IntegerParseError(v.clone(), relocate_span(span, from, to))
+        }
+        Error::InvalidCharacter(v, pos) => Error::InvalidCharacter(*v, relocate(pos, from, to)),
+        Error::InvalidEscape(v, span) => {
+            Error::InvalidEscape(v.clone(), relocate_span(span, from, to))
+        }
+        Error::Io(kind, message, pos) => Error::Io(*kind, message.clone(), relocate(pos, from, to)),
+        Error::MapOddEntries(span) => Error::MapOddEntries(relocate_span(span, from, to)),
+        Error::MismatchedDelimiter(v, pos) => {
+            Error::MismatchedDelimiter(*v, relocate(pos, from, to))
+        }
+        Error::QuoteWithoutValue(span) => Error::QuoteWithoutValue(relocate_span(span, from, to)),
+        Error::RatioParseError(v, span) => {
+            Error::RatioParseError(v.clone(), relocate_span(span, from, to))
+        }
+        Error::RatioZeroDenominator(v, span) => {
+            Error::RatioZeroDenominator(v.clone(), relocate_span(span, from, to))
+        }
+        Error::SymbolParseError(v, span) => {
+            Error::SymbolParseError(v.clone(), relocate_span(span, from, to))
+        }
+        Error::TagWithoutValue(span) => Error::TagWithoutValue(relocate_span(span, from, to)),
+        Error::UnbalancedParentheses(pos) => Error::UnbalancedParentheses(relocate(pos, from, to)),
+        Error::UnterminatedBlockComment(span) => {
+            Error::UnterminatedBlockComment(relocate_span(span, from, to))
+        }
+        Error::UnterminatedString(span) => Error::UnterminatedString(relocate_span(span, from, to)),
+        Error::Utf8Error(span) => Error::Utf8Error(relocate_span(span, from, to)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::iter::PositionIterator;
+    use super::super::parser::Parser;
+    use super::super::pos::span;
+
+    /// Applies the edit both incrementally and from scratch and compares the results.
+    fn check(text: &str, range: Span, new_text: &str, expected: &str) {
+        let mut doc = Document::new(text.into());
+        doc.edit(&range, new_text);
+
+        assert_eq!(expected, doc.text());
+        assert_eq!(expected, doc.syntax().to_string());
+        assert_eq!(Document::new(expected.into()).line_starts, doc.line_starts);
+
+        let (items, errors) = Parser::from(expected).parse_recovering();
+        let (cst_items, cst_errors) = cst::parse(expected).to_items_recovering();
+
+        assert_eq!(cst_items, doc.items());
+        assert_eq!(cst_errors, doc.errors());
+
+        if errors.is_empty() {
+            assert_eq!(items, doc.items());
+        }
+    }
+
+    #[test]
+    fn edit_atom() {
+        check(
+            "(a b)\n(c d)\n(e f)",
+            span(1, 3, 1, 4),
+            "xyz",
+            "(a b)\n(c xyz)\n(e f)",
+        );
+    }
+
+    #[test]
+    fn edit_same_line() {
+        check(
+            "(a) (b) (c)",
+            span(0, 1, 0, 2),
+            "foo bar",
+            "(foo bar) (b) (c)",
+        );
+    }
+
+    #[test]
+    fn edit_insert_lines() {
+        check(
+            "(a)\n\n(b)\n(c)",
+            span(1, 0, 1, 0),
+            "x\ny\n",
+            "(a)\nx\ny\n\n(b)\n(c)",
+        );
+    }
+
+    #[test]
+    fn edit_delete_lines() {
+        check("(a)\n(b\n c)\n(d)", span(0, 3, 2, 3), "", "(a)\n(d)");
+    }
+
+    #[test]
+    fn edit_merge_atoms() {
+        check("a b c", span(0, 1, 0, 2), "", "ab c");
+    }
+
+    #[test]
+    fn edit_open_list() {
+        check("(a)\n(b)\n(c)", span(1, 0, 1, 1), "((", "(a)\n((b)\n(c)");
+    }
+
+    #[test]
+    fn edit_close_list() {
+        check("(a\n(b)\n(c)", span(0, 2, 0, 2), ")", "(a)\n(b)\n(c)");
+    }
+
+    #[test]
+    fn edit_open_string() {
+        check("a\nb \"c\"\nd", span(0, 1, 0, 1), " \"", "a \"\nb \"c\"\nd");
+    }
+
+    #[test]
+    fn edit_comment_out() {
+        check("(a)b c\nd", span(0, 0, 0, 1), ";", ";a)b c\nd");
+        check("a ; b \nc", span(0, 2, 0, 3), "", "a  b \nc");
+    }
+
+    #[test]
+    fn edit_block_comment() {
+        check(
+            "a\nb #| c |#\nd",
+            span(0, 1, 0, 1),
+            " #|",
+            "a #|\nb #| c |#\nd",
+        );
+        check("#| a |# b", span(0, 6, 0, 7), "", "#| a | b");
+    }
+
+    #[test]
+    fn edit_datum_comment() {
+        check("#_ a\nb", span(0, 3, 0, 4), "", "#_ \nb");
+        check("#_ (a)\nb", span(0, 5, 0, 6), "", "#_ (a\nb");
+    }
+
+    #[test]
+    fn edit_line_endings() {
+        check("(a)\r\n(b)", span(1, 1, 1, 2), "c", "(a)\r\n(c)");
+        check("(a)\r(b)", span(0, 3, 1, 0), "\r\n\t", "(a)\r\n\t(b)");
+        check("; a\r\nb", span(1, 0, 1, 0), "c", "; a\r\ncb");
+    }
+
+    #[test]
+    fn edit_strings() {
+        check("#\"a\"# b", span(0, 4, 0, 5), "", "#\"a\" b");
+        check("\"\" \"a\"", span(0, 2, 0, 3), "", "\"\"\"a\"");
+        check("\"\"\"\na\"\"\" b", span(1, 1, 1, 2), "", "\"\"\"\na\"\" b");
+    }
+
+    #[test]
+    fn edit_errors() {
+        check(
+            "(a)\n01 (b)\n(c",
+            span(0, 1, 0, 2),
+            "02",
+            "(02)\n01 (b)\n(c",
+        );
+    }
+
+    #[test]
+    fn edit_empty() {
+        check("", span(0, 0, 0, 0), "(a)", "(a)");
+        check("(a)", span(0, 0, 0, 3), "", "");
+    }
+
+    #[test]
+    fn edit_random() {
+        let alphabet = [
+            "(", ")", "\"", ";", "\n", "\r\n", "\r", "\t", " ", "a", "0", "\\", "è", "#|", "|#",
+            "#_", "#\"", "\"#", "\"\"\"",
+        ];
+
+        edit_randomly(
+            Options::default(),
+            &alphabet,
+            "(define (f x)\n  ; comment\n  (g \"x\" 1))\n\n(f 2)\n",
+        );
+    }
+
+    #[test]
+    fn edit_random_extended() {
+        let alphabet = [
+            "(", ")", "[", "]", "{", "}", "#{", "\"", ";", "\n", " ", "a", "0", "\\", "#_", "#a",
+            "'", "`", ",", ",@", "1.5", "3/4",
+        ];
+
+        edit_randomly(
+            Options::extended(),
+            &alphabet,
+            "(define [f x]\n  ; comment\n  {:g #inst \"x\" :h '#{1}})\n\n`(f ,@a)\n",
+        );
+    }
+
+    /// Applies random edits made of the given fragments both incrementally and from scratch, and
+    /// compares the results.
+    fn edit_randomly(options: Options, alphabet: &[&str], text: &str) {
+        // A tiny linear congruential generator, good enough to shuffle edits around.
+        let mut seed: u32 = 42;
+        let mut random = |n: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as usize % n
+        };
+
+        let mut text = String::from(text);
+        let mut doc = Document::with_options(text.clone(), options.clone());
+
+        for _ in 0..500 {
+            let mut start = random(text.len() + 1);
+            let mut end = (start + random(3)).min(text.len());
+
+            while !text.is_char_boundary(start) {
+                start -= 1;
+            }
+
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+
+            // Positions can't point between the two bytes of a CRLF sequence.
+            if is_crlf_split(&text, start) {
+                start -= 1;
+            }
+
+            if is_crlf_split(&text, end) {
+                end += 1;
+            }
+
+            let new_text = (0..random(3))
+                .map(|_| alphabet[random(alphabet.len())])
+                .collect::<String>();
+
+            let start_pos = pos_of(&text, start);
+            let end_pos = pos_of(&text, end);
+
+            text.replace_range(start..end, &new_text);
+            doc.edit(&Span::new(start_pos, end_pos), &new_text);
+
+            assert_eq!(text, doc.text());
+            assert_eq!(Document::new(text.clone()).line_starts, doc.line_starts);
+            assert_eq!(
+                cst::parse_with_options(&text, options.clone()).green(),
+                doc.syntax().green()
+            );
+
+            let (items, errors) =
+                cst::parse_with_options(&text, options.clone()).to_items_recovering();
+            assert_eq!(items, doc.items(), "{:?}", text);
+            assert_eq!(errors, doc.errors(), "{:?}", text);
+        }
+    }
+
+    fn is_crlf_split(text: &str, offset: usize) -> bool {
+        offset > 0 && text.as_bytes()[offset - 1..].starts_with(b"\r\n")
+    }
+
+    fn pos_of(text: &str, offset: usize) -> Position {
+        let mut iter = PositionIterator::new(text[..offset].bytes());
+        iter.by_ref().last();
+        iter.pos().clone()
+    }
+
+    #[test]
+    fn edit_end() {
+        check("(a)\n(b)", span(1, 3, 1, 3), " c", "(a)\n(b) c");
+        check("(a)\n(b)", span(5, 0, 5, 0), "\nc", "(a)\n(b)\nc");
+    }
+}
@@ -0,0 +1,409 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Neg;
+use std::str::FromStr;
+
+/// An integer of arbitrary precision.
+///
+/// Values that fit in an `i64` are always stored as `Small`, which provides a fast path for the
+/// common case and guarantees that every value has exactly one representation.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Integer {
+    Small(i64),
+    Big(BigInt),
+}
+
+/// An integer that doesn't fit in an `i64`, stored as a sign and a magnitude.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BigInt {
+    negative: bool,
+    /// Base 2^32 digits, least significant first, without trailing zeros.
+    magnitude: Vec<u32>,
+}
+
+/// The radix an integer literal was written in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+/// The error returned when a string doesn't represent a valid integer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseIntegerError;
+
+impl Integer {
+    /// Parses an integer written in the given `radix` (between 2 and 36), with an optional sign.
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<Self, ParseIntegerError> {
+        assert!((2..=36).contains(&radix), "radix must be between 2 and 36");
+
+        if let Ok(v) = i64::from_str_radix(s, radix) {
+            return Ok(Integer::Small(v));
+        }
+
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        if digits.is_empty() {
+            return Err(ParseIntegerError);
+        }
+
+        let mut magnitude = vec![];
+
+        for ch in digits.chars() {
+            let digit = ch.to_digit(radix).ok_or(ParseIntegerError)?;
+            mul_add(&mut magnitude, radix, digit);
+        }
+
+        Ok(Self::from_parts(negative, magnitude))
+    }
+
+    /// Returns the value as an `i64`, if it fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Integer::Small(v) => Some(*v),
+            Integer::Big(_) => None,
+        }
+    }
+
+    /// Returns the value as a `u64`, if it fits.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Integer::Small(v) => u64::try_from(*v).ok(),
+            Integer::Big(v) if v.negative || v.magnitude.len() > 2 => None,
+            Integer::Big(v) => Some(
+                v.magnitude
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, &digit| (acc << 32) | u64::from(digit)),
+            ),
+        }
+    }
+
+    /// Returns `true` if the value is less than zero.
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Integer::Small(v) => *v < 0,
+            Integer::Big(v) => v.negative,
+        }
+    }
+
+    /// Returns the value written in the given `radix` (between 2 and 36), using lowercase letters
+    /// for digits above 9.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "radix must be between 2 and 36");
+
+        let (negative, mut magnitude) = match self {
+            Integer::Small(v) if radix == 10 => return v.to_string(),
+            Integer::Small(v) => {
+                let abs = v.unsigned_abs();
+                (*v < 0, vec![abs as u32, (abs >> 32) as u32])
+            }
+            Integer::Big(v) => (v.negative, v.magnitude.clone()),
+        };
+
+        let mut digits = vec![];
+
+        loop {
+            let digit = div_rem(&mut magnitude, radix);
+            digits.push(std::char::from_digit(digit, radix).expect("valid digit"));
+
+            if magnitude.iter().all(|&limb| limb == 0) {
+                break;
+            }
+        }
+
+        if negative {
+            digits.push('-');
+        }
+
+        digits.iter().rev().collect()
+    }
+
+    //
+    // Private
+    //
+
+    /// Builds an integer from its sign and magnitude, falling back to `Small` when it fits.
+    fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+
+        if magnitude.len() <= 2 {
+            let v = magnitude
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &limb| (acc << 32) | u64::from(limb));
+
+            if !negative && v <= i64::MAX as u64 {
+                return Integer::Small(v as i64);
+            } else if negative && v <= i64::MIN.unsigned_abs() {
+                return Integer::Small((v as i64).wrapping_neg());
+            }
+        }
+
+        Integer::Big(BigInt {
+            negative,
+            magnitude,
+        })
+    }
+}
+
+impl Radix {
+    /// Returns the numeric value of the radix.
+    pub fn value(self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hexadecimal => 16,
+        }
+    }
+}
+
+impl FromStr for Integer {
+    type Err = ParseIntegerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str_radix(s, 10)
+    }
+}
+
+impl fmt::Display for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str_radix(10))
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Integer::Small(a), Integer::Small(b)) => a.cmp(b),
+            // Big values are always outside of the range of small ones.
+            (Integer::Small(_), Integer::Big(b)) => {
+                if b.negative {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                }
+            }
+            (Integer::Big(_), Integer::Small(_)) => other.cmp(self).reverse(),
+            (Integer::Big(a), Integer::Big(b)) => match (a.negative, b.negative) {
+                (false, true) => Ordering::Greater,
+                (true, false) => Ordering::Less,
+                (false, false) => cmp_magnitude(&a.magnitude, &b.magnitude),
+                (true, true) => cmp_magnitude(&b.magnitude, &a.magnitude),
+            },
+        }
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<i32> for Integer {
+    fn from(v: i32) -> Self {
+        Integer::Small(i64::from(v))
+    }
+}
+
+impl From<i64> for Integer {
+    fn from(v: i64) -> Self {
+        Integer::Small(v)
+    }
+}
+
+impl From<u64> for Integer {
+    fn from(v: u64) -> Self {
+        Self::from_parts(false, vec![v as u32, (v >> 32) as u32])
+    }
+}
+
+impl Neg for Integer {
+    type Output = Integer;
+
+    fn neg(self) -> Integer {
+        match self {
+            Integer::Small(v) => {
+                let abs = v.unsigned_abs();
+                Self::from_parts(v > 0, vec![abs as u32, (abs >> 32) as u32])
+            }
+            Integer::Big(v) => Self::from_parts(!v.negative, v.magnitude),
+        }
+    }
+}
+
+impl fmt::Display for ParseIntegerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid integer literal")
+    }
+}
+
+//
+// Private
+//
+
+/// Multiplies `magnitude` by `mul` and adds `add` to it, in place.
+fn mul_add(magnitude: &mut Vec<u32>, mul: u32, add: u32) {
+    let mut carry = u64::from(add);
+
+    for limb in magnitude.iter_mut() {
+        let v = u64::from(*limb) * u64::from(mul) + carry;
+        *limb = v as u32;
+        carry = v >> 32;
+    }
+
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides `magnitude` by `div` in place, returning the remainder.
+fn div_rem(magnitude: &mut [u32], div: u32) -> u32 {
+    let mut rem = 0u64;
+
+    for limb in magnitude.iter_mut().rev() {
+        let v = (rem << 32) | u64::from(*limb);
+        *limb = (v / u64::from(div)) as u32;
+        rem = v % u64::from(div);
+    }
+
+    rem as u32
+}
+
+/// Compares two magnitudes without trailing zeros.
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_small() {
+        assert_eq!(Ok(Integer::Small(0)), "0".parse());
+        assert_eq!(Ok(Integer::Small(-25)), "-25".parse());
+        assert_eq!(Ok(Integer::Small(i64::MAX)), "9223372036854775807".parse());
+        assert_eq!(Ok(Integer::Small(i64::MIN)), "-9223372036854775808".parse());
+    }
+
+    #[test]
+    fn parse_big() {
+        let v: Integer = "9223372036854775808".parse().unwrap();
+        assert_eq!(None, v.as_i64());
+        assert_eq!(Some(9_223_372_036_854_775_808), v.as_u64());
+        assert!(!v.is_negative());
+        assert_eq!("9223372036854775808", v.to_string());
+
+        let v: Integer = "-340282366920938463463374607431768211457".parse().unwrap();
+        assert!(v.is_negative());
+        assert_eq!(None, v.as_u64());
+        assert_eq!("-340282366920938463463374607431768211457", v.to_string());
+
+        assert_eq!(
+            Integer::from(u64::MAX),
+            "18446744073709551615".parse().unwrap()
+        );
+        assert_eq!(Some(u64::MAX), Integer::from(u64::MAX).as_u64());
+        assert_eq!(
+            None,
+            "18446744073709551616".parse::<Integer>().unwrap().as_u64()
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(Err(ParseIntegerError), "".parse::<Integer>());
+        assert_eq!(Err(ParseIntegerError), "-".parse::<Integer>());
+        assert_eq!(Err(ParseIntegerError), "12a".parse::<Integer>());
+        assert_eq!(
+            Err(ParseIntegerError),
+            "99999999999999999999-".parse::<Integer>()
+        );
+    }
+
+    #[test]
+    fn radix() {
+        let v = Integer::from_str_radix("ffffffffffffffffffff", 16).unwrap();
+        assert_eq!("ffffffffffffffffffff", v.to_str_radix(16));
+        assert_eq!("1208925819614629174706175", v.to_string());
+
+        assert_eq!("-101", Integer::from(-5).to_str_radix(2));
+        assert_eq!(
+            "-1000000000000000000000000000000000000000000000000000000000000000",
+            Integer::Small(i64::MIN).to_str_radix(2)
+        );
+    }
+
+    #[test]
+    fn ordering() {
+        let mut values: Vec<Integer> = [
+            "100000000000000000000",
+            "-5",
+            "-100000000000000000000",
+            "0",
+            "-200000000000000000000",
+            "9223372036854775807",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+
+        values.sort();
+
+        assert_eq!(
+            vec![
+                "-200000000000000000000",
+                "-100000000000000000000",
+                "-5",
+                "0",
+                "9223372036854775807",
+                "100000000000000000000",
+            ],
+            values.iter().map(Integer::to_string).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn negate() {
+        let parse = |s: &str| s.parse::<Integer>().unwrap();
+
+        assert_eq!(Integer::from(0), -Integer::from(0));
+        assert_eq!(Integer::from(-5), -Integer::from(5));
+        assert_eq!(Integer::from(5), -Integer::from(-5));
+        assert_eq!(parse("9223372036854775808"), -Integer::from(i64::MIN));
+        assert_eq!(Integer::from(i64::MIN), -parse("9223372036854775808"));
+        assert_eq!(
+            parse("-100000000000000000000"),
+            -parse("100000000000000000000")
+        );
+    }
+}
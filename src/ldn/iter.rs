@@ -13,17 +13,28 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::io;
+use std::io::BufRead;
+use std::mem;
 use std::str;
+use std::sync::{Arc, Mutex};
 
 use super::pos::Position;
 
-/// Wraps an iterator over bytes to add position tracking.
+/// Wraps an iterator over UTF-8 encoded bytes to add position tracking. Columns are counted in
+/// characters.
+///
+/// Lines end with either `\n`, `\r\n` or a lone `\r`. Tabs count as a single column unless a
+/// tab width is set, in which case they advance to the next tab stop.
 pub struct PositionIterator<I>
 where
     I: Iterator<Item = u8>,
 {
     iter: I,
     pos: Position,
+    tab_width: Option<usize>,
+    /// Set after a carriage return, so that the line feed of a CRLF sequence isn't counted again.
+    after_cr: bool,
 }
 
 impl<I> PositionIterator<I>
@@ -32,12 +43,25 @@ where
 {
     /// Creates a new position-tracking iterator by wrapping the given `iter`.
     pub fn new(iter: I) -> Self {
+        Self::with_position(iter, Position::default())
+    }
+
+    /// Creates a new position-tracking iterator by wrapping the given `iter`, starting to count
+    /// from `pos` instead of the beginning of the document.
+    pub fn with_position(iter: I, pos: Position) -> Self {
         Self {
             iter,
-            pos: Position::default(),
+            pos,
+            tab_width: None,
+            after_cr: false,
         }
     }
 
+    /// Sets the distance between tab stops, or counts tabs as a single column when `None`.
+    pub fn set_tab_width(&mut self, tab_width: Option<usize>) {
+        self.tab_width = tab_width;
+    }
+
     /// Returns the current position.
     pub fn pos(&self) -> &Position {
         &self.pos
@@ -53,12 +77,22 @@ where
     fn next(&mut self) -> Option<I::Item> {
         let ch = self.iter.next();
 
+        let after_cr = mem::replace(&mut self.after_cr, ch == Some(b'\r'));
+
         match ch {
             None => (),
-            Some(ch) if ch == b'\n' => {
+            // The line feed of a CRLF sequence, already counted with the carriage return.
+            Some(b'\n') if after_cr => (),
+            Some(b'\n') | Some(b'\r') => {
                 self.pos.line += 1;
                 self.pos.column = 0;
             }
+            Some(b'\t') => match self.tab_width {
+                Some(width) if width > 0 => self.pos.column = (self.pos.column / width + 1) * width,
+                _ => self.pos.column += 1,
+            },
+            // Continuation bytes belong to the same UTF-8 encoded character as the byte before.
+            Some(ch) if ch & 0xc0 == 0x80 => (),
             Some(_) => self.pos.column += 1,
         }
 
@@ -73,6 +107,76 @@ impl<'a> From<&'a str> for PositionIterator<str::Bytes<'a>> {
     }
 }
 
+/// Adapts a buffered reader into an iterator over bytes.
+///
+/// Iteration stops at the first I/O error (except `Interrupted`, which is retried), which is kept
+/// aside to be retrieved through the `ReadError` returned by `error()`.
+pub struct ReadBytes<R>
+where
+    R: BufRead,
+{
+    reader: R,
+    error: ReadError,
+}
+
+impl<R> ReadBytes<R>
+where
+    R: BufRead,
+{
+    /// Creates a new byte iterator reading from the given buffered reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            error: ReadError::default(),
+        }
+    }
+
+    /// Returns a handle to the error that stopped iteration, if any.
+    pub fn error(&self) -> ReadError {
+        self.error.clone()
+    }
+}
+
+impl<R> Iterator for ReadBytes<R>
+where
+    R: BufRead,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok([]) => return None,
+                Ok(buf) => {
+                    let ch = buf[0];
+                    self.reader.consume(1);
+                    return Some(ch);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.error.set(err);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// A shared handle to the I/O error that stopped a `ReadBytes` iterator.
+#[derive(Clone, Default)]
+pub struct ReadError(Arc<Mutex<Option<io::Error>>>);
+
+impl ReadError {
+    /// Takes the error out of the handle, if any.
+    pub fn take(&self) -> Option<io::Error> {
+        self.0.lock().expect("read error lock").take()
+    }
+
+    fn set(&self, err: io::Error) {
+        *self.0.lock().expect("read error lock") = Some(err);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,6 +208,20 @@ mod tests {
         assert_eq!(0, p.pos.column);
     }
 
+    #[test]
+    fn with_position() {
+        let mut p = PositionIterator::with_position("foo\nbar".bytes(), Position::new(2, 5));
+        p.by_ref().take(3).last();
+
+        assert_eq!(2, p.pos.line);
+        assert_eq!(8, p.pos.column);
+
+        p.by_ref().last();
+
+        assert_eq!(3, p.pos.line);
+        assert_eq!(3, p.pos.column);
+    }
+
     #[test]
     fn two_lines() {
         let mut p = PositionIterator::from("foo\nbar");
@@ -112,4 +230,51 @@ mod tests {
         assert_eq!(1, p.pos.line);
         assert_eq!(3, p.pos.column);
     }
+
+    #[test]
+    fn line_endings() {
+        let mut p = PositionIterator::from("a\r\nb\rc\n\r\n");
+        p.by_ref().take(3).last();
+
+        assert_eq!(Position::new(1, 0), p.pos);
+
+        p.by_ref().take(3).last();
+
+        assert_eq!(Position::new(2, 1), p.pos);
+
+        p.by_ref().last();
+
+        assert_eq!(Position::new(4, 0), p.pos);
+    }
+
+    #[test]
+    fn tabs() {
+        let mut p = PositionIterator::from("\ta\tbcde\t");
+        p.by_ref().last();
+
+        assert_eq!(Position::new(0, 8), p.pos);
+
+        let mut p = PositionIterator::from("\ta\tbcde\t");
+        p.set_tab_width(Some(4));
+        p.by_ref().last();
+
+        assert_eq!(Position::new(0, 16), p.pos);
+    }
+
+    #[test]
+    fn multibyte() {
+        let mut p = PositionIterator::from("è😀");
+        p.by_ref().last();
+
+        assert_eq!(0, p.pos.line);
+        assert_eq!(2, p.pos.column);
+    }
+
+    #[test]
+    fn read_bytes() {
+        let mut r = ReadBytes::new(&b"foo"[..]);
+
+        assert_eq!(b"foo".to_vec(), r.by_ref().collect::<Vec<u8>>());
+        assert!(r.error().take().is_none());
+    }
 }
@@ -16,15 +16,39 @@
 //! This module provides a parser and formatter (pretty-printer) for Lithium Data Notation.
 
 pub mod ast;
+pub mod builder;
+pub mod cst;
+pub mod de;
 pub mod error;
+pub mod events;
 pub mod fmt;
+pub mod fold;
+pub mod incremental;
+pub mod integer;
 pub mod iter;
+pub mod options;
 pub mod parser;
 pub mod pos;
+pub mod query;
+pub mod ser;
+pub mod tags;
 pub mod tokenizer;
+pub mod value;
+pub mod visit;
 
-pub use self::ast::{Atom, Item, List};
+pub use self::ast::{Atom, Item, List, Quote, StringStyle};
+pub use self::builder::Builder;
+pub use self::de::{from_item, from_str};
 pub use self::error::{Error, Result};
+pub use self::events::Event;
 pub use self::fmt::fmt;
+pub use self::fold::Fold;
+pub use self::integer::{Integer, Radix};
+pub use self::options::Options;
 pub use self::parser::Parser;
 pub use self::pos::{Position, Span};
+pub use self::query::Query;
+pub use self::ser::{to_item, to_string};
+pub use self::tags::TagRegistry;
+pub use self::value::Value;
+pub use self::visit::Visitor;
@@ -0,0 +1,51 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Parser options, selecting the dialect of LDN being parsed.
+///
+/// The default options describe strict LDN, as specified by the grammar. Each extension to the
+/// grammar must be enabled explicitly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    /// Accepts floating-point numbers (`1.5`, `-2e10`) and ratios (`3/4`).
+    pub floats_and_ratios: bool,
+    /// Accepts vectors (`[1 2]`), maps (`{:a 1}`) and sets (`#{1 2}`).
+    pub collections: bool,
+    /// Accepts tagged elements (`#inst "1985-04-12T23:20:50.52Z"`).
+    pub tagged_elements: bool,
+    /// Accepts the quote (`'x`), quasiquote (`` `x ``), unquote (`,x`) and unquote-splicing
+    /// (`,@x`) shorthands.
+    pub quotes: bool,
+    /// Parses `true`, `false` and `nil` as plain symbols rather than as booleans and nil, for
+    /// documents written before they were part of the grammar. Not enabled by `extended()`.
+    pub literals_as_symbols: bool,
+    /// The distance between tab stops used when reporting columns. Tabs count as a single column
+    /// when `None`, like language server clients expect.
+    pub tab_width: Option<usize>,
+}
+
+impl Options {
+    /// Returns options enabling every extension to the strict LDN grammar.
+    pub fn extended() -> Self {
+        Self {
+            floats_and_ratios: true,
+            collections: true,
+            tagged_elements: true,
+            quotes: true,
+            literals_as_symbols: false,
+            tab_width: None,
+        }
+    }
+}
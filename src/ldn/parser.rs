@@ -13,13 +13,23 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::io;
+use std::iter;
+use std::mem;
 use std::str;
 
-use super::ast::{Atom, Item, List};
+use super::ast::{Atom, Item, List, Quote, StringStyle};
 use super::error::{Error, Result};
-use super::iter::PositionIterator;
-use super::pos::Span;
+use super::events::{Event, Events};
+use super::integer::{Integer, Radix};
+use super::iter::{PositionIterator, ReadBytes, ReadError};
+use super::options::Options;
+use super::pos::{Position, Span};
+use super::tags::TagRegistry;
 use super::tokenizer::Tokenizer;
+use super::value::Value;
 
 /// Lithium Platform Data Notation parser.
 pub struct Parser<I>
@@ -27,6 +37,18 @@ where
     I: Iterator<Item = u8>,
 {
     tokenizer: Tokenizer<I>,
+    options: Options,
+    tags: TagRegistry,
+    /// Lists and collections opened and not closed yet, innermost last.
+    open: Vec<Collection>,
+    /// Position where the event being parsed begins.
+    event_start: Position,
+    recovering: bool,
+    errors: Vec<Error>,
+    /// Set once iterating over top-level items has returned an error or reached the end.
+    done: bool,
+    /// Where to look for I/O errors when reading from an `io::Read`.
+    read_error: Option<ReadError>,
 }
 
 impl<I> Parser<I>
@@ -35,295 +57,2149 @@ where
 {
     /// Creates a new parser using the given byte iterator.
     pub fn new(iter: I) -> Self {
+        Self::with_start(iter, Position::default())
+    }
+
+    /// Creates a new parser using the given byte iterator, reporting positions as if the stream
+    /// started at `start` rather than at the beginning of a document.
+    pub fn with_start(iter: I, start: Position) -> Self {
         Self {
-            tokenizer: Tokenizer::new(PositionIterator::new(iter)),
+            tokenizer: Tokenizer::new(PositionIterator::with_position(iter, start.clone())),
+            options: Options::default(),
+            tags: TagRegistry::default(),
+            open: vec![],
+            event_start: start,
+            recovering: false,
+            errors: vec![],
+            done: false,
+            read_error: None,
         }
     }
 
+    /// Sets the options selecting the dialect of LDN to parse. Strict LDN is parsed by default.
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.tokenizer.set_tab_width(options.tab_width);
+        self.options = options;
+        self
+    }
+
+    /// Sets the handlers run on the values of tagged elements, which must be enabled in the
+    /// parser's options.
+    pub fn with_tags(mut self, tags: TagRegistry) -> Self {
+        self.tags = tags;
+        self
+    }
+
     /// Parses the given byte stream.
+    ///
+    /// Parsers are also iterators over top-level items, which can be used to process large
+    /// streams one item at a time instead of collecting all of them at once.
     pub fn parse(&mut self) -> Result<List> {
-        self.parse_items(true)
+        self.collect()
+    }
+
+    /// Parses the given byte stream, recovering from errors instead of stopping at the first one.
+    ///
+    /// Malformed input is skipped up to the next delimiter (whitespace or parens) and kept in the
+    /// returned list as an `Item::Error`, while lists left open at the end of the stream are
+    /// implicitly closed. Returns the partial list of items together with every error found.
+    pub fn parse_recovering(&mut self) -> (List, Vec<Error>) {
+        self.recovering = true;
+
+        let items = self
+            .by_ref()
+            .collect::<Result<List>>()
+            .expect("recovering parser never fails");
+
+        (items, self.errors.drain(..).collect())
+    }
+
+    /// Returns an iterator over the parsing events of the byte stream.
+    ///
+    /// Unlike `parse()`, this doesn't build any tree: memory usage stays constant no matter how
+    /// large the input is. The iterator stops after returning the first error.
+    pub fn events(&mut self) -> Events<'_, I> {
+        Events::new(self)
+    }
+
+    /// Returns the next parsing event, or `None` at the end of the stream.
+    pub(crate) fn next_event(&mut self) -> Option<Result<Event>> {
+        let event = self.parse_event();
+
+        // An event parsed while reaching the end of the stream because of a read failure may have
+        // been cut short, so it's replaced by the failure itself.
+        match self.read_error.as_ref().and_then(ReadError::take) {
+            None => event,
+            Some(err) => Some(Err(Error::Io(
+                err.kind(),
+                err.to_string(),
+                self.tokenizer.pos().clone(),
+            ))),
+        }
+    }
+
+    /// Parses the next event, or returns `None` at the end of the stream.
+    fn parse_event(&mut self) -> Option<Result<Event>> {
+        while let Some(&ch) = self.tokenizer.peek_ch() {
+            if !is_whitespace(ch) {
+                break;
+            }
+
+            self.tokenizer.next_ch();
+        }
+
+        self.event_start = self.tokenizer.pos().clone();
+
+        if self.recovering {
+            self.tokenizer.start_recording();
+        }
+
+        if self.tokenizer.peek_ch().is_none() {
+            if let Some(err) = self.read_error.as_ref().and_then(ReadError::take) {
+                let err = Error::Io(err.kind(), err.to_string(), self.event_start.clone());
+
+                if !self.recovering {
+                    return Some(Err(err));
+                }
+
+                self.errors.push(err);
+            }
+        }
+
+        let ch = match self.tokenizer.peek_ch() {
+            Some(&ch) => ch,
+            None if self.open.is_empty() => return None,
+            None => {
+                // We reached the end of the stream without encountering a closing paren.
+                let err = Error::UnbalancedParentheses(self.event_start.clone());
+
+                if !self.recovering {
+                    return Some(Err(err));
+                }
+
+                // Keep the stream of events balanced by implicitly closing the list.
+                self.errors.push(err);
+
+                let collection = self.open.pop().expect("open collection");
+                let span = Span::new(self.event_start.clone(), self.event_start.clone());
+
+                return Some(Ok(collection.end_event(span)));
+            }
+        };
+
+        let collections = self.options.collections;
+        let quotes = self.options.quotes;
+
+        Some(match ch {
+            // Comments
+            ';' => self
+                .parse_comment()
+                .map(|(comment, span)| Event::Comment(comment, span)),
+            // Numbers, or symbols starting with a sign or a dot not followed by a digit.
+            ch if ch.is_ascii_digit() || ch == '-' || ch == '+' || ch == '.' => {
+                self.parse_integer_or_symbol().map(Event::Atom)
+            }
+            // Strings and characters
+            '"' => self.parse_string().map(Event::Atom),
+            '\\' => self.parse_char().map(Event::Atom),
+            // Keywords and symbols
+            ':' => self.parse_keyword().map(Event::Atom),
+            ch if is_symbol_start(ch) => self.parse_symbol().map(Event::Atom),
+            // Quote shorthands
+            '\'' | '`' | ',' if quotes => Ok(self.parse_quote()),
+            // Lists and collections
+            '(' => Ok(self.start_collection(Collection::List)),
+            '[' if collections => Ok(self.start_collection(Collection::Vector)),
+            '{' if collections => Ok(self.start_collection(Collection::Map)),
+            '#' => self.parse_dispatch(),
+            ch if is_closing(ch, collections) => self.end_collection(ch),
+            // Catch-all error
+            _ => Err(self.tokenizer.invalid_ch()),
+        })
     }
 
     //
     // Private
     //
 
-    /// Parses an item. This is essentially the same as parsing a list, the only difference is that
-    /// top-level items are not enclosed by parens.
-    fn parse_items(&mut self, is_top_level: bool) -> Result<List> {
+    /// Builds the next top-level item from parsing events, or returns `None` at the end of the
+    /// stream.
+    fn next_item(&mut self) -> Option<Result<Item>> {
+        // Items of the lists and collections being parsed, along with the position where they
+        // start.
+        let mut stack: Vec<(List, Position)> = vec![];
+        // Tags, quote shorthands and datum comments waiting for their value, along with the depth
+        // of the stack where they appear.
+        let mut prefixes: Vec<(Prefix, Span, usize)> = vec![];
         let mut ret = vec![];
 
-        while let Some(&ch) = self.tokenizer.peek_ch() {
-            match ch {
-                // Whitespace and comments
-                _ if Self::is_whitespace(ch) => {
-                    self.tokenizer.next_ch();
+        while let Some(event) = self.next_event() {
+            let mut item = match event {
+                Ok(Event::StartList(span))
+                | Ok(Event::StartVector(span))
+                | Ok(Event::StartMap(span))
+                | Ok(Event::StartSet(span)) => {
+                    stack.push((mem::take(&mut ret), span.start));
                     continue;
                 }
-                b';' => {
-                    ret.push(self.parse_comment()?);
-                }
-                // Integers (or symbols if the token is '-' alone).
-                ch if ch == b'0' || ch == b'-' || Self::is_digit_1_9(ch) => {
-                    ret.push(self.parse_integer_or_symbol()?);
+                Ok(event @ Event::EndList(_))
+                | Ok(event @ Event::EndVector(_))
+                | Ok(event @ Event::EndMap(_))
+                | Ok(event @ Event::EndSet(_)) => {
+                    if let Err(err) = self.drop_prefixes(&mut prefixes, stack.len()) {
+                        return Some(Err(err));
+                    }
+
+                    let (parent, start) = stack.pop().expect("balanced list events");
+                    let items = mem::replace(&mut ret, parent);
+                    let span = Span::new(start, event.span().end.clone());
+
+                    match self.build_collection(Collection::of(&event), items, span) {
+                        Ok(item) => item,
+                        Err(err) => return Some(Err(err)),
+                    }
                 }
-                // Strings
-                b'"' => {
-                    ret.push(self.parse_string()?);
+                Ok(Event::Tag(tag, span)) => {
+                    prefixes.push((Prefix::Tag(tag), span, stack.len()));
+                    continue;
                 }
-                // Keywords and symbols
-                b':' => {
-                    ret.push(self.parse_keyword()?);
+                Ok(Event::Quote(quote, span)) => {
+                    prefixes.push((Prefix::Quote(quote), span, stack.len()));
+                    continue;
                 }
-                ch if Self::is_symbol(ch) => {
-                    ret.push(self.parse_symbol()?);
+                Ok(Event::DatumComment(span)) => {
+                    prefixes.push((Prefix::DatumComment, span, stack.len()));
+                    continue;
                 }
-                // Lists
-                b'(' => {
-                    let list_start = self.tokenizer.pos().clone();
-                    self.tokenizer.next_ch();
-                    ret.push(Item::List(
-                        self.parse_items(false)?,
-                        Span::new(list_start, self.tokenizer.pos().clone()),
-                    ));
+                Ok(Event::Atom(atom)) => Item::Atom(atom),
+                Ok(Event::Comment(_, _)) | Ok(Event::BlockComment(_, _))
+                    if has_prefixes(&prefixes, stack.len()) =>
+                {
+                    // Comments between a prefix and its value are discarded.
+                    continue;
                 }
-                b')' => {
-                    self.tokenizer.next_ch();
-                    return Ok(ret);
+                Ok(Event::Comment(comment, span)) => Item::Comment(comment, span),
+                Ok(Event::BlockComment(comment, span)) => Item::BlockComment(comment, span),
+                Err(err) => match self.recover(err) {
+                    Ok(item) => item,
+                    Err(err) => return Some(Err(err)),
+                },
+            };
+
+            while has_prefixes(&prefixes, stack.len()) {
+                let (prefix, span, _) = prefixes.pop().expect("pending prefix");
+
+                item = match prefix {
+                    Prefix::Tag(tag) => match self.build_tagged(tag, span, item) {
+                        Ok(item) => item,
+                        Err(err) => return Some(Err(err)),
+                    },
+                    Prefix::Quote(quote) => {
+                        let span = Span::new(span.start, item.span().end.clone());
+                        Item::Quoted(quote, Box::new(item), span)
+                    }
+                    Prefix::DatumComment => {
+                        let span = Span::new(span.start, item.span().end.clone());
+                        Item::DatumComment(Box::new(item), span)
+                    }
+                };
+
+                if item.is_comment() {
+                    break;
                 }
-                // Catch-all error
-                ch => {
-                    return Err(Error::InvalidCharacter(ch, self.tokenizer.pos().clone()));
+            }
+
+            if item.is_comment() && has_prefixes(&prefixes, stack.len()) {
+                // Datum comments are discarded as well when found before the value of a prefix.
+                continue;
+            }
+
+            if stack.is_empty() {
+                return Some(Ok(item));
+            }
+
+            ret.push(item);
+        }
+
+        match self.drop_prefixes(&mut prefixes, 0) {
+            Ok(()) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// Recovers from the given error by skipping to the next delimiter and returning everything
+    /// consumed since the start of the failed event as an `Item::Error`. Simply returns the error
+    /// when not in recovering mode.
+    fn recover(&mut self, err: Error) -> Result<Item> {
+        if !self.recovering {
+            return Err(err);
+        }
+
+        self.errors.push(err);
+
+        let collections = self.options.collections;
+
+        while let Some(&ch) = self.tokenizer.peek_ch() {
+            if is_whitespace(ch) || is_opening(ch, collections) || is_closing(ch, collections) {
+                break;
+            }
+
+            self.tokenizer.next_ch();
+        }
+
+        let text = self.tokenizer.stop_recording();
+
+        Ok(Item::Error(text, self.event_span()))
+    }
+
+    /// Returns the span from the start of the current event to the current position.
+    fn event_span(&self) -> Span {
+        Span::new(self.event_start.clone(), self.tokenizer.pos().clone())
+    }
+
+    /// Consumes the opening delimiter of a list or collection and returns its start event.
+    fn start_collection(&mut self, collection: Collection) -> Event {
+        self.tokenizer.next_ch();
+        self.open.push(collection);
+
+        collection.start_event(self.event_span())
+    }
+
+    /// Consumes the closing delimiter `ch` and returns the end event of the innermost list or
+    /// collection, if `ch` matches its opening delimiter.
+    fn end_collection(&mut self, ch: char) -> Result<Event> {
+        self.tokenizer.next_ch();
+
+        match self.open.last() {
+            // A closing delimiter without a matching opening one.
+            None => Err(Error::UnbalancedParentheses(self.event_start.clone())),
+            Some(collection) if collection.closing() != ch => {
+                Err(Error::MismatchedDelimiter(ch, self.event_start.clone()))
+            }
+            Some(_) => {
+                let collection = self.open.pop().expect("open collection");
+                Ok(collection.end_event(self.event_span()))
+            }
+        }
+    }
+
+    /// Wraps the given value in a tagged element, running the handler registered for the tag.
+    fn build_tagged(&mut self, tag: String, tag_span: Span, value: Item) -> Result<Item> {
+        let span = Span::new(tag_span.start.clone(), value.span().end.clone());
+
+        let value = match self.tags.handle(&tag, &value) {
+            Ok(value) => value,
+            Err(message) => {
+                let err = Error::InvalidTaggedValue(tag.clone(), message, tag_span);
+
+                if !self.recovering {
+                    return Err(err);
                 }
+
+                self.errors.push(err);
+                value
+            }
+        };
+
+        Ok(Item::Tagged(tag, Box::new(value), span))
+    }
+
+    /// Drops the prefixes found at the given depth of the stack, which are left without a
+    /// value when their list or collection is closed or the stream ends.
+    fn drop_prefixes(
+        &mut self,
+        prefixes: &mut Vec<(Prefix, Span, usize)>,
+        depth: usize,
+    ) -> Result<()> {
+        while has_prefixes(prefixes, depth) {
+            let err = match prefixes.pop().expect("pending prefix") {
+                (Prefix::Tag(_), span, _) => Error::TagWithoutValue(span),
+                (Prefix::Quote(_), span, _) => Error::QuoteWithoutValue(span),
+                (Prefix::DatumComment, span, _) => Error::DatumCommentWithoutValue(span),
+            };
+
+            if !self.recovering {
+                return Err(err);
             }
+
+            self.errors.push(err);
         }
 
-        // If we get here we are parsing a list and we didn't encounter a closing parens.
-        if !is_top_level {
-            return Err(Error::UnbalancedParentheses(self.tokenizer.pos().clone()));
+        Ok(())
+    }
+
+    /// Builds a list or collection from its items, checking that maps have an even number of
+    /// entries and that neither map keys nor set elements are repeated.
+    fn build_collection(
+        &mut self,
+        collection: Collection,
+        items: List,
+        span: Span,
+    ) -> Result<Item> {
+        let err = match collection {
+            Collection::List => None,
+            Collection::Vector => None,
+            Collection::Map => check_map(&items, &span),
+            Collection::Set => check_set(&items),
+        };
+
+        if let Some(err) = err {
+            if !self.recovering {
+                return Err(err);
+            }
+
+            self.errors.push(err);
         }
 
-        Ok(ret)
+        Ok(collection.item(items, span))
     }
 
     // Productions
 
+    /// Parses a block or datum comment, or the opening delimiter of a set or a tag, depending on
+    /// the character following the hash. Called by the main loop at the hash's position.
+    fn parse_dispatch(&mut self) -> Result<Event> {
+        self.tokenizer.next_ch();
+
+        match self.tokenizer.peek_ch().cloned() {
+            Some('|') => self.parse_block_comment(),
+            Some('#') | Some('"') => self.parse_raw_string().map(Event::Atom),
+            Some('_') => {
+                self.tokenizer.next_ch();
+                Ok(Event::DatumComment(self.event_span()))
+            }
+            Some('{') if self.options.collections => Ok(self.start_collection(Collection::Set)),
+            Some(ch) if self.options.tagged_elements && is_alpha(ch) => self.parse_tag(),
+            _ => Err(Error::InvalidCharacter('#', self.event_start.clone())),
+        }
+    }
+
+    /// Parses a tag. Called by `parse_dispatch()` at the first character after the hash.
+    fn parse_tag(&mut self) -> Result<Event> {
+        let (tag, _) = self.next_token()?;
+
+        if !is_qualified_symbol(&tag) {
+            return Err(Error::SymbolParseError(tag, self.event_span()));
+        }
+
+        Ok(Event::Tag(tag, self.event_span()))
+    }
+
+    /// Parses a raw string, delimited by quotation marks and by as many hashes as precede the
+    /// opening one. Called by `parse_dispatch()` at the character following the first hash.
+    fn parse_raw_string(&mut self) -> Result<Atom> {
+        let mut hashes = 1;
+
+        while self.tokenizer.peek_ch() == Some(&'#') {
+            self.tokenizer.next_ch();
+            hashes += 1;
+        }
+
+        match self.tokenizer.peek_ch() {
+            Some('"') => self.tokenizer.next_ch(),
+            Some(_) => return Err(self.tokenizer.invalid_ch()),
+            None => return Err(Error::InvalidCharacter('#', self.event_start.clone())),
+        };
+
+        let mut string = String::new();
+
+        loop {
+            let pos = self.tokenizer.pos().clone();
+
+            self.tokenizer
+                .check_ch(|ch| is_printable(ch) || is_whitespace(ch))?;
+
+            match self.tokenizer.next_ch() {
+                None => {
+                    return Err(Error::UnterminatedString(Span::new(
+                        self.event_start.clone(),
+                        pos,
+                    )));
+                }
+                Some('"') => {
+                    let mut closing = 0;
+
+                    while closing < hashes && self.tokenizer.peek_ch() == Some(&'#') {
+                        self.tokenizer.next_ch();
+                        closing += 1;
+                    }
+
+                    if closing == hashes {
+                        break;
+                    }
+
+                    string.push('"');
+                    string.extend(iter::repeat_n('#', closing));
+                }
+                // Line breaks are normalized to line feeds.
+                Some('\r') => {
+                    if self.tokenizer.peek_ch() == Some(&'\n') {
+                        self.tokenizer.next_ch();
+                    }
+
+                    string.push('\n');
+                }
+                Some(ch) => string.push(ch),
+            }
+        }
+
+        Ok(Atom::String(
+            string,
+            StringStyle::Raw(hashes),
+            self.event_span(),
+        ))
+    }
+
+    /// Parses a quote shorthand. Called by the main loop at the shorthand's position.
+    fn parse_quote(&mut self) -> Event {
+        let quote = match self.tokenizer.next_ch() {
+            Some('\'') => Quote::Quote,
+            Some('`') => Quote::Quasiquote,
+            _ if self.tokenizer.peek_ch() == Some(&'@') => {
+                self.tokenizer.next_ch();
+                Quote::UnquoteSplicing
+            }
+            _ => Quote::Unquote,
+        };
+
+        Event::Quote(quote, self.event_span())
+    }
+
+    /// Parses a block comment, which may contain nested block comments. Called by
+    /// `parse_dispatch()` at the opening bar's position.
+    fn parse_block_comment(&mut self) -> Result<Event> {
+        // Skip the opening bar.
+        self.tokenizer.next_ch();
+
+        let mut comment = String::new();
+        let mut depth = 1;
+
+        loop {
+            self.tokenizer
+                .check_ch(|ch| is_printable(ch) || is_whitespace(ch))?;
+
+            let ch = match self.tokenizer.next_ch() {
+                Some(ch) => ch,
+                None => return Err(Error::UnterminatedBlockComment(self.event_span())),
+            };
+
+            let next = match (ch, self.tokenizer.peek_ch()) {
+                ('#', Some('|')) => '|',
+                ('|', Some('#')) => '#',
+                _ => {
+                    comment.push(ch);
+                    continue;
+                }
+            };
+
+            self.tokenizer.next_ch();
+
+            if next == '|' {
+                depth += 1;
+            } else {
+                depth -= 1;
+
+                if depth == 0 {
+                    break;
+                }
+            }
+
+            comment.push(ch);
+            comment.push(next);
+        }
+
+        Ok(Event::BlockComment(comment, self.event_span()))
+    }
+
     /// Parses a comment. Called by the main loop at the semicolon's position.
-    fn parse_comment(&mut self) -> Result<Item> {
+    fn parse_comment(&mut self) -> Result<(String, Span)> {
         let (comment, span) = self
             .tokenizer
-            .take_until(Self::is_printable, |ch| ch != b'\n')?;
+            .take_until(|ch| is_printable(ch) || ch == '\t', |ch| !is_newline(ch))?;
 
-        Ok(Item::Comment(
-            comment.trim_start_matches(';').trim().to_string(),
-            span,
-        ))
+        Ok((comment.trim_start_matches(';').trim().to_string(), span))
     }
 
-    /// Parses an integer. Called by the main loop at the first digit or negative sign position.
-    fn parse_integer_or_symbol(&mut self) -> Result<Item> {
+    /// Parses an integer, or a floating-point number or ratio when enabled, unless the token is a
+    /// symbol. Called by the main loop at the first digit, sign or dot position.
+    fn parse_integer_or_symbol(&mut self) -> Result<Atom> {
         let (token, span) = self.next_token()?;
 
-        if (token.starts_with('0') && token != "0") || token.starts_with("-0") {
-            return Err(Error::IntegerLeadingZero(token, span));
-        } else if token == "-" {
-            // "-" is a valid symbol when found by itself.
-            return Ok(Item::Atom(Atom::Symbol(token, span)));
+        if !is_numeric(&token) {
+            // Signs and dots start a symbol unless followed by a digit, like in "-" or "-x".
+            return parse_symbol(token, span);
         }
 
-        match token.parse::<isize>() {
-            Ok(val) => Ok(Item::Atom(Atom::Integer(val, span))),
-            Err(_) => Err(Error::IntegerParseError(token, span)),
+        if self.options.floats_and_ratios {
+            if token.contains('/') {
+                return parse_ratio(token, span);
+            } else if is_float(&token) {
+                return parse_float(token, span);
+            }
         }
+
+        parse_integer(token, span)
     }
 
     /// Parses a string. Called by the main loop at the opening quotation mark's position.
-    fn parse_string(&mut self) -> Result<Item> {
+    fn parse_string(&mut self) -> Result<Atom> {
         // The real starting position includes the position of the opening quotation marks.
         let string_start = self.tokenizer.pos().clone();
 
         // Skip opening quotation marks.
         self.tokenizer.next_ch();
 
-        // Accumulate chunks until we encounter non-escaped quotation marks.
+        // Two quotation marks are either an empty string or the beginning of a text block.
+        if self.tokenizer.peek_ch() == Some(&'"') {
+            self.tokenizer.next_ch();
+
+            if self.tokenizer.peek_ch() == Some(&'"') {
+                return self.parse_text_block(string_start);
+            }
+
+            let span = Span::new(string_start, self.tokenizer.pos().clone());
+
+            return Ok(Atom::String(String::new(), StringStyle::Plain, span));
+        }
+
+        // Accumulate characters until we encounter non-escaped quotation marks.
         let mut string = String::new();
-        let mut string_last_span: Span;
 
         loop {
-            let (chunk, span) = self
-                .tokenizer
-                .take_until(Self::is_printable, |ch| ch != b'"')?;
+            let pos = self.tokenizer.pos().clone();
 
-            // Skip quotation marks.
-            self.tokenizer.next_ch();
+            self.tokenizer
+                .check_ch(|ch| is_printable(ch) || ch == '\t')?;
 
-            string_last_span = span;
+            match self.tokenizer.peek_ch() {
+                None => {
+                    return Err(Error::UnterminatedString(Span::new(string_start, pos)));
+                }
+                Some('"') => {
+                    self.tokenizer.next_ch();
+                    break;
+                }
+                Some('\\') => {
+                    self.tokenizer.next_ch();
+                    let ch = self.parse_escape(pos)?;
+                    string.push(ch);
+                }
+                Some(&ch) => {
+                    self.tokenizer.next_ch();
+                    string.push(ch);
+                }
+            }
+        }
 
-            if chunk.ends_with('\\') {
-                string += chunk[..chunk.len() - 1].as_ref();
-                string += "\"";
-            } else {
-                string += chunk.as_ref();
-                break;
+        let span = Span::new(string_start, self.tokenizer.pos().clone());
+
+        Ok(Atom::String(string, StringStyle::Plain, span))
+    }
+
+    /// Parses a text block. Called by `parse_string()` at the last quotation mark of the opening
+    /// delimiter, which must be followed by a line break.
+    fn parse_text_block(&mut self, string_start: Position) -> Result<Atom> {
+        self.tokenizer.next_ch();
+
+        match self.tokenizer.peek_ch().cloned() {
+            Some(ch) if is_newline(ch) => {
+                self.tokenizer.next_ch();
+
+                if ch == '\r' && self.tokenizer.peek_ch() == Some(&'\n') {
+                    self.tokenizer.next_ch();
+                }
+            }
+            Some(_) => return Err(self.tokenizer.invalid_ch()),
+            None => {
+                let span = Span::new(string_start, self.tokenizer.pos().clone());
+
+                return Err(Error::UnterminatedString(span));
             }
         }
 
-        Ok(Item::Atom(Atom::String(
-            string,
-            Span::new(string_start, string_last_span.end),
-        )))
+        // Characters of each line, marked as escaped or not. Escaped whitespace is never stripped.
+        let mut lines: Vec<Vec<(char, bool)>> = vec![vec![]];
+
+        loop {
+            let pos = self.tokenizer.pos().clone();
+
+            self.tokenizer
+                .check_ch(|ch| is_printable(ch) || is_whitespace(ch))?;
+
+            let ch = match self.tokenizer.next_ch() {
+                Some(ch) => ch,
+                None => return Err(Error::UnterminatedString(Span::new(string_start, pos))),
+            };
+
+            match ch {
+                '"' => {
+                    let mut quotes = 1;
+
+                    while quotes < 3 && self.tokenizer.peek_ch() == Some(&'"') {
+                        self.tokenizer.next_ch();
+                        quotes += 1;
+                    }
+
+                    if quotes == 3 {
+                        break;
+                    }
+
+                    let line = lines.last_mut().expect("current line");
+                    line.extend(iter::repeat_n(('"', false), quotes));
+                }
+                '\\' => {
+                    let escaped = self.parse_escape(pos)?;
+                    let line = lines.last_mut().expect("current line");
+                    line.push((escaped, true));
+                }
+                '\r' | '\n' => {
+                    if ch == '\r' && self.tokenizer.peek_ch() == Some(&'\n') {
+                        self.tokenizer.next_ch();
+                    }
+
+                    lines.push(vec![]);
+                }
+                _ => {
+                    let line = lines.last_mut().expect("current line");
+                    line.push((ch, false));
+                }
+            }
+        }
+
+        let span = Span::new(string_start, self.tokenizer.pos().clone());
+
+        Ok(Atom::String(
+            strip_indentation(&lines),
+            StringStyle::Block,
+            span,
+        ))
+    }
+
+    /// Parses an escape sequence within a string, returning the escaped character. Called by
+    /// `parse_string()` right after the backslash, which starts at `escape_start`.
+    fn parse_escape(&mut self, escape_start: Position) -> Result<char> {
+        let mut sequence = String::from("\\");
+
+        let ch = self.next_escape_ch(&escape_start, &mut sequence)?;
+
+        let escaped = match ch {
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            'u' => self.parse_unicode_escape(&escape_start, &mut sequence)?,
+            _ => None,
+        };
+
+        escaped.ok_or_else(|| {
+            Error::InvalidEscape(
+                sequence,
+                Span::new(escape_start, self.tokenizer.pos().clone()),
+            )
+        })
+    }
+
+    /// Parses the `{XXXX}` part of a `\u{XXXX}` escape sequence, where `XXXX` are one to six
+    /// hexadecimal digits. Returns `None` if the digits don't denote a Unicode scalar value.
+    fn parse_unicode_escape(
+        &mut self,
+        escape_start: &Position,
+        sequence: &mut String,
+    ) -> Result<Option<char>> {
+        if self.next_escape_ch(escape_start, sequence)? != '{' {
+            return Ok(None);
+        }
+
+        let mut digits = String::new();
+
+        loop {
+            match self.next_escape_ch(escape_start, sequence)? {
+                '}' => break,
+                ch if ch.is_ascii_hexdigit() && digits.len() < 6 => digits.push(ch),
+                _ => return Ok(None),
+            }
+        }
+
+        Ok(u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(std::char::from_u32))
+    }
+
+    /// Consumes the next character of an escape sequence, appending it to `sequence`.
+    fn next_escape_ch(&mut self, escape_start: &Position, sequence: &mut String) -> Result<char> {
+        let pos = self.tokenizer.pos().clone();
+
+        self.tokenizer.check_ch(is_printable)?;
+
+        match self.tokenizer.next_ch() {
+            None => Err(Error::InvalidEscape(
+                sequence.clone(),
+                Span::new(escape_start.clone(), pos),
+            )),
+            Some(ch) => {
+                sequence.push(ch);
+                Ok(ch)
+            }
+        }
     }
 
     /// Parses a keyword. Called by the main loop at the colon's position. Defers to
     /// `parse_symbol()` for actual parsing.
-    fn parse_keyword(&mut self) -> Result<Item> {
+    fn parse_keyword(&mut self) -> Result<Atom> {
         // The real starting position includes the colon peeked by the main loop.
         let keyword_start = self.tokenizer.pos().clone();
 
         // Skip colon peeked by main loop.
         self.tokenizer.next_ch();
 
-        match self.parse_symbol() {
-            Ok(Item::Atom(Atom::Symbol(sym, span))) => Ok(Item::Atom(Atom::Keyword(
-                sym,
-                Span::new(keyword_start, span.end),
-            ))),
-            v => v,
+        let (token, span) = self.next_token()?;
+
+        // Unlike symbols, keywords can't be made of the slash alone.
+        if token == "/" || !is_qualified_symbol(&token) {
+            return Err(Error::SymbolParseError(token, span));
         }
+
+        Ok(Atom::Keyword(token, Span::new(keyword_start, span.end)))
     }
 
-    /// Parses a symbol.
-    fn parse_symbol(&mut self) -> Result<Item> {
+    /// Parses a symbol, optionally qualified by a namespace, or a boolean or nil unless they are
+    /// parsed as symbols.
+    fn parse_symbol(&mut self) -> Result<Atom> {
         let (token, span) = self.next_token()?;
 
-        if !token.bytes().by_ref().all(Self::is_symbol) {
-            return Err(Error::SymbolParseError(token, span));
+        if !self.options.literals_as_symbols {
+            match token.as_str() {
+                "true" => return Ok(Atom::Bool(true, span)),
+                "false" => return Ok(Atom::Bool(false, span)),
+                "nil" => return Ok(Atom::Nil(span)),
+                _ => {}
+            }
         }
 
-        Ok(Item::Atom(Atom::Symbol(token, span)))
+        parse_symbol(token, span)
     }
 
-    // Token Helpers
+    /// Parses a character. Called by the main loop at the backslash's position.
+    fn parse_char(&mut self) -> Result<Atom> {
+        // Skip the backslash.
+        self.tokenizer.next_ch();
 
-    /// Returns the next token and span, by consuming bytes until the first whitespace character or
-    /// closing paren.
-    fn next_token(&mut self) -> Result<(String, Span)> {
-        Ok(self.tokenizer.take_until(Self::is_printable, |ch| {
-            !Self::is_whitespace(ch) && ch != b')'
-        })?)
-    }
+        // The first character is taken as is, even if it's a delimiter like in "\)".
+        let first = match self.tokenizer.peek_ch().cloned() {
+            Some(ch) if !is_whitespace(ch) => ch,
+            _ => return Err(Error::CharParseError("\\".into(), self.event_span())),
+        };
 
-    // Recognizers
+        self.tokenizer.check_ch(is_printable)?;
+        self.tokenizer.next_ch();
+
+        let mut token = first.to_string();
+
+        // The closing brace of a Unicode escape would end the token when collections are enabled.
+        if first == 'u' && self.tokenizer.peek_ch() == Some(&'{') {
+            let collections = self.options.collections;
+            let (escape, _) = self.tokenizer.take_until(is_printable, |ch| {
+                !is_whitespace(ch) && ch != '}' && !is_closing(ch, collections)
+            })?;
+
+            token.push_str(&escape);
+
+            if self.tokenizer.peek_ch() == Some(&'}') {
+                self.tokenizer.next_ch();
+                token.push('}');
+            }
+        }
+
+        let (rest, _) = self.next_token()?;
+        token.push_str(&rest);
+
+        parse_char(token, self.event_span())
+    }
+
+    // Token Helpers
+
+    /// Returns the next token and span, by consuming characters until the first whitespace
+    /// character or closing delimiter.
+    fn next_token(&mut self) -> Result<(String, Span)> {
+        let collections = self.options.collections;
+
+        self.tokenizer.take_until(is_printable, |ch| {
+            !is_whitespace(ch) && !is_closing(ch, collections)
+        })
+    }
+}
+
+//
+// Recognizers
+//
+
+/// Returns `true` if `ch` is a printable character, that is any character except for control
+/// characters.
+fn is_printable(ch: char) -> bool {
+    !ch.is_control()
+}
+
+/// Returns `true` if `ch` is considered a whitespace character according to the grammar.
+pub(crate) fn is_whitespace(ch: char) -> bool {
+    matches!(ch, ' ' | '\t' | '\n' | '\r' | '\x0c')
+}
+
+/// Returns `true` if `ch` ends a line, on its own or as part of a CRLF sequence.
+pub(crate) fn is_newline(ch: char) -> bool {
+    ch == '\n' || ch == '\r'
+}
+
+/// Returns `true` if `ch` opens a list or, when enabled, a collection.
+fn is_opening(ch: char, collections: bool) -> bool {
+    ch == '(' || (collections && (ch == '[' || ch == '{'))
+}
+
+/// Returns `true` if `ch` closes a list or, when enabled, a collection.
+pub(crate) fn is_closing(ch: char, collections: bool) -> bool {
+    ch == ')' || (collections && (ch == ']' || ch == '}'))
+}
+
+/// Returns `true` if `ch` can start a symbol.
+fn is_symbol_start(ch: char) -> bool {
+    match ch {
+        '+' | '-' | '*' | '/' | '%' | '=' | '<' | '>' | '?' | '!' | '.' | '_' | '&' | '$' => true,
+        _ => is_alpha(ch),
+    }
+}
+
+/// Returns `true` if `ch` is a symbol constituent, that is a character that can appear in a symbol
+/// after the first one.
+fn is_symbol(ch: char) -> bool {
+    is_symbol_start(ch) || ch.is_ascii_digit() || ch == '\''
+}
+
+/// Returns `true` if `token` denotes a number rather than a symbol, that is if it starts with a
+/// digit, or with a sign or a dot followed by a digit.
+fn is_numeric(token: &str) -> bool {
+    let mut chars = token.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(ch), _) if ch.is_ascii_digit() => true,
+        (Some('-'), Some(ch)) | (Some('+'), Some(ch)) | (Some('.'), Some(ch)) => {
+            ch.is_ascii_digit()
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if `token` is a valid symbol: a name, a namespace and a name separated by a
+/// slash, or the slash alone. Neither the namespace nor the name can be empty, contain a slash or
+/// denote a number.
+pub(crate) fn is_qualified_symbol(token: &str) -> bool {
+    let is_name = |s: &str| {
+        s.chars().next().is_some_and(is_symbol_start)
+            && s.chars().all(|ch| ch != '/' && is_symbol(ch))
+            && !is_numeric(s)
+    };
+
+    match token.split_once('/') {
+        _ if token == "/" => true,
+        Some((namespace, name)) => is_name(namespace) && is_name(name),
+        None => is_name(token),
+    }
+}
+
+/// Returns `true` if `ch` is an alphabetic character, either ASCII (`a` to `z` lowercase or
+/// uppercase) or any other Unicode letter.
+pub(crate) fn is_alpha(ch: char) -> bool {
+    ch.is_alphabetic()
+}
+
+/// Returns `true` if `token` is a floating-point number rather than an integer, that is if it's
+/// written in decimal and has either a fractional part or an exponent.
+fn is_float(token: &str) -> bool {
+    let unsigned = token.strip_prefix(['-', '+']).unwrap_or(token);
+
+    !unsigned.starts_with("0b")
+        && !unsigned.starts_with("0o")
+        && !unsigned.starts_with("0x")
+        && unsigned.contains(['.', 'e', 'E'])
+}
+
+/// Returns `true` if `s` is a non-empty sequence of decimal digits.
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|ch| ch.is_ascii_digit())
+}
+
+/// Returns `true` if `s` is an unsigned decimal integer without leading zeros.
+fn is_decimal(s: &str) -> bool {
+    is_digits(s) && (s == "0" || !s.starts_with('0'))
+}
+
+//
+// Collections
+//
+
+/// The kinds of lists and collections, which differ in their delimiters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Collection {
+    List,
+    Vector,
+    Map,
+    Set,
+}
+
+impl Collection {
+    /// Returns the kind of list or collection closed by the given end event.
+    fn of(event: &Event) -> Self {
+        match event {
+            Event::EndList(_) => Collection::List,
+            Event::EndVector(_) => Collection::Vector,
+            Event::EndMap(_) => Collection::Map,
+            Event::EndSet(_) => Collection::Set,
+            _ => unreachable!("not an end event"),
+        }
+    }
+
+    /// Returns the closing delimiter.
+    fn closing(self) -> char {
+        match self {
+            Collection::List => ')',
+            Collection::Vector => ']',
+            Collection::Map | Collection::Set => '}',
+        }
+    }
+
+    fn start_event(self, span: Span) -> Event {
+        match self {
+            Collection::List => Event::StartList(span),
+            Collection::Vector => Event::StartVector(span),
+            Collection::Map => Event::StartMap(span),
+            Collection::Set => Event::StartSet(span),
+        }
+    }
+
+    fn end_event(self, span: Span) -> Event {
+        match self {
+            Collection::List => Event::EndList(span),
+            Collection::Vector => Event::EndVector(span),
+            Collection::Map => Event::EndMap(span),
+            Collection::Set => Event::EndSet(span),
+        }
+    }
+
+    fn item(self, items: List, span: Span) -> Item {
+        match self {
+            Collection::List => Item::List(items, span),
+            Collection::Vector => Item::Vector(items, span),
+            Collection::Map => Item::Map(items, span),
+            Collection::Set => Item::Set(items, span),
+        }
+    }
+}
+
+/// A tag, quote shorthand or datum comment, waiting for the value it applies to.
+enum Prefix {
+    Tag(String),
+    Quote(Quote),
+    DatumComment,
+}
+
+/// Returns `true` if the innermost pending prefix appears at the given depth of the stack.
+fn has_prefixes(prefixes: &[(Prefix, Span, usize)], depth: usize) -> bool {
+    prefixes
+        .last()
+        .is_some_and(|(_, _, prefix_depth)| *prefix_depth == depth)
+}
+
+/// Checks that the map has an even number of entries and no repeated keys.
+pub(crate) fn check_map(items: &[Item], span: &Span) -> Option<Error> {
+    let entries = items
+        .iter()
+        .filter(|item| !item.is_comment())
+        .collect::<Vec<_>>();
+
+    if entries.len() % 2 != 0 {
+        return Some(Error::MapOddEntries(span.clone()));
+    }
+
+    find_duplicate(entries.into_iter().step_by(2))
+        .map(|key| Error::DuplicateKey(key.span().clone()))
+}
+
+/// Checks that the set has no repeated elements.
+pub(crate) fn check_set(items: &[Item]) -> Option<Error> {
+    find_duplicate(items.iter().filter(|item| !item.is_comment()))
+        .map(|element| Error::DuplicateElement(element.span().clone()))
+}
+
+/// Returns the first item denoting the same value as one of the items before it. Malformed items
+/// don't denote a value, and are never duplicates.
+fn find_duplicate<'a, I>(items: I) -> Option<&'a Item>
+where
+    I: Iterator<Item = &'a Item>,
+{
+    let mut seen = HashSet::new();
+
+    for item in items {
+        if let Ok(value) = Value::try_from(item) {
+            if !seen.insert(value) {
+                return Some(item);
+            }
+        }
+    }
+
+    None
+}
+
+//
+// Literals
+//
+
+/// Parses an integer token.
+///
+/// Integers may be written in binary (`0b`), octal (`0o`) or hexadecimal (`0x`) and may contain
+/// underscores between digits. A redundant `+` sign is allowed, while zero can't be negative
+/// whatever the radix.
+pub(crate) fn parse_integer(token: String, span: Span) -> Result<Atom> {
+    let (negative, unsigned) = match (token.strip_prefix('-'), token.strip_prefix('+')) {
+        (Some(unsigned), _) => (true, unsigned),
+        (None, Some(unsigned)) => (false, unsigned),
+        (None, None) => (false, token.as_str()),
+    };
+
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0b") => (Radix::Binary, &unsigned[2..]),
+        Some("0o") => (Radix::Octal, &unsigned[2..]),
+        Some("0x") => (Radix::Hexadecimal, &unsigned[2..]),
+        _ => (Radix::Decimal, unsigned),
+    };
+
+    if digits.is_empty() {
+        return Err(Error::IntegerMissingDigits(token, span));
+    } else if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Err(Error::IntegerMisplacedSeparator(token, span));
+    }
+
+    let digits = digits.replace('_', "");
+
+    if !digits.chars().all(|ch| ch.is_digit(radix.value())) {
+        return Err(Error::IntegerParseError(token, span));
+    } else if (radix == Radix::Decimal && digits.starts_with('0') && digits != "0")
+        || (negative && digits.chars().all(|ch| ch == '0'))
+    {
+        return Err(Error::IntegerLeadingZero(token, span));
+    }
+
+    let digits = if negative {
+        format!("-{}", digits)
+    } else {
+        digits
+    };
+
+    let val = match Integer::from_str_radix(&digits, radix.value()) {
+        Ok(val) => val,
+        Err(_) => return Err(Error::IntegerParseError(token, span)),
+    };
+
+    let literal = if token == val.to_string() {
+        None
+    } else {
+        Some(token)
+    };
+
+    Ok(Atom::Integer(val, literal, span))
+}
+
+/// Parses a symbol token, optionally qualified by a namespace.
+fn parse_symbol(token: String, span: Span) -> Result<Atom> {
+    if !is_qualified_symbol(&token) {
+        return Err(Error::SymbolParseError(token, span));
+    }
+
+    Ok(Atom::Symbol(token, span))
+}
+
+/// Joins the lines of a text block after removing trailing whitespace and the indentation shared
+/// by non-blank lines and by the line of the closing delimiter.
+fn strip_indentation(lines: &[Vec<(char, bool)>]) -> String {
+    let is_space = |&(ch, escaped): &(char, bool)| !escaped && (ch == ' ' || ch == '\t');
+    let last = lines.len() - 1;
+
+    let indentation = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, line)| *i == last || !line.iter().all(is_space))
+        .map(|(_, line)| line.iter().take_while(|ch| is_space(ch)).count())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| {
+            let end = line.len() - line.iter().rev().take_while(|ch| is_space(ch)).count();
+
+            line[indentation.min(end)..end]
+                .iter()
+                .map(|&(ch, _)| ch)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses a character token, without the leading backslash: either a single character, the name
+/// of a whitespace character or a `u{XXXX}` escape.
+fn parse_char(token: String, span: Span) -> Result<Atom> {
+    let mut chars = token.chars();
+
+    let ch = match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => match token.as_str() {
+            "newline" => Some('\n'),
+            "return" => Some('\r'),
+            "space" => Some(' '),
+            "tab" => Some('\t'),
+            _ => token
+                .strip_prefix("u{")
+                .and_then(|token| token.strip_suffix('}'))
+                .filter(|digits| {
+                    (1..=6).contains(&digits.len())
+                        && digits.chars().all(|ch| ch.is_ascii_hexdigit())
+                })
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .and_then(std::char::from_u32),
+        },
+    };
+
+    match ch {
+        Some(ch) => Ok(Atom::Char(ch, span)),
+        None => Err(Error::CharParseError(format!("\\{}", token), span)),
+    }
+}
+
+/// Parses a floating-point number token: a decimal integer followed by a fractional part, an
+/// exponent, or both.
+pub(crate) fn parse_float(token: String, span: Span) -> Result<Atom> {
+    let unsigned = token.strip_prefix(['-', '+']).unwrap_or(&token);
+
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (unsigned, None),
+    };
+
+    let (integral, fractional) = match mantissa.split_once('.') {
+        Some((integral, fractional)) => (integral, Some(fractional)),
+        None => (mantissa, None),
+    };
+
+    let exponent = exponent.map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent));
+
+    let is_valid =
+        is_decimal(integral) && fractional.is_none_or(is_digits) && exponent.is_none_or(is_digits);
+
+    match token.parse::<f64>() {
+        Ok(val) if is_valid && val.is_finite() => {
+            let literal = if token == format!("{:?}", val) {
+                None
+            } else {
+                Some(token)
+            };
+
+            Ok(Atom::Float(val, literal, span))
+        }
+        _ => Err(Error::FloatParseError(token, span)),
+    }
+}
+
+/// Parses a ratio token: two decimal integers separated by a slash, where only the numerator can
+/// be signed.
+fn parse_ratio(token: String, span: Span) -> Result<Atom> {
+    let parts = token.split_once('/').filter(|(numerator, denominator)| {
+        is_decimal(numerator.strip_prefix(['-', '+']).unwrap_or(numerator))
+            && is_decimal(denominator)
+    });
+
+    let (numerator, denominator) = match parts {
+        Some((numerator, denominator)) if numerator != "-0" => (numerator, denominator),
+        _ => return Err(Error::RatioParseError(token, span)),
+    };
+
+    let numerator = numerator.parse().expect("valid numerator");
+    let denominator: Integer = denominator.parse().expect("valid denominator");
+
+    if denominator == Integer::from(0) {
+        return Err(Error::RatioZeroDenominator(token, span));
+    }
+
+    Ok(Atom::Ratio(numerator, denominator, span))
+}
+
+impl<R> Parser<ReadBytes<R>>
+where
+    R: io::BufRead,
+{
+    /// Creates a new parser reading from the given buffered reader. Read failures are reported as
+    /// `Error::Io`, at the position where they occurred.
+    pub fn from_buf_reader(reader: R) -> Self {
+        let iter = ReadBytes::new(reader);
+        let read_error = iter.error();
+
+        let mut parser = Self::new(iter);
+        parser.read_error = Some(read_error);
+        parser
+    }
+}
+
+impl<R> Parser<ReadBytes<io::BufReader<R>>>
+where
+    R: io::Read,
+{
+    /// Creates a new parser reading from the given reader, which is wrapped in a buffered reader.
+    /// Read failures are reported as `Error::Io`, at the position where they occurred.
+    pub fn from_reader(reader: R) -> Self {
+        Self::from_buf_reader(io::BufReader::new(reader))
+    }
+}
+
+/// Iterates over top-level items, parsing them lazily. Iteration stops after the first error.
+impl<I> Iterator for Parser<I>
+where
+    I: Iterator<Item = u8>,
+{
+    type Item = Result<Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let item = self.next_item();
+
+        if let None | Some(Err(_)) = item {
+            self.done = true;
+        }
+
+        item
+    }
+}
+
+impl<'a> From<&'a str> for Parser<str::Bytes<'a>> {
+    fn from(v: &'a str) -> Self {
+        Self::new(v.bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::pos::{pos, span};
+
+    #[test]
+    fn parse_empty() {
+        assert!(Parser::from("").parse().unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_unknown() {
+        assert_eq!(
+            Err(Error::InvalidCharacter('\u{7}', pos(0, 1))),
+            Parser::from(" \u{7}").parse()
+        );
+    }
+
+    #[test]
+    fn parse_comment() {
+        assert_eq!(
+            vec![
+                Item::Comment("foo bar".into(), span(0, 2, 0, 11)),
+                Item::Comment("baz".into(), span(1, 0, 1, 5)),
+                Item::Comment("qux".into(), span(2, 0, 2, 6)),
+                Item::Comment("; quux ;".into(), span(3, 0, 3, 11))
+            ],
+            Parser::from("  ; foo bar\n; baz\n;; qux\n;; ; quux ;")
+                .parse()
+                .unwrap()
+        )
+    }
+
+    #[test]
+    fn parse_whitespace() {
+        assert_eq!(
+            vec![
+                Item::Comment("a".into(), span(0, 0, 0, 3)),
+                Item::List(
+                    vec![
+                        Item::Atom(Atom::Symbol("b".into(), span(1, 2, 1, 3))),
+                        Item::Atom(Atom::Symbol("c".into(), span(2, 0, 2, 1))),
+                    ],
+                    span(1, 0, 3, 1)
+                ),
+            ],
+            Parser::from("; a\r\n(\tb\x0c\rc\r\n)").parse().unwrap()
+        );
+
+        assert_eq!(
+            vec![Item::Atom(Atom::Symbol("a".into(), span(1, 8, 1, 9)))],
+            Parser::from("\r\n\ta")
+                .with_options(Options {
+                    tab_width: Some(8),
+                    ..Options::default()
+                })
+                .parse()
+                .unwrap()
+        );
+
+        // Tabs are allowed in comments and strings.
+        assert_eq!(
+            vec![
+                Item::Comment("a\tb".into(), span(0, 0, 0, 5)),
+                Item::Atom(Atom::String(
+                    "c\td".into(),
+                    StringStyle::Plain,
+                    span(1, 0, 1, 5)
+                )),
+            ],
+            Parser::from("; a\tb\n\"c\td\"").parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_block_comment() {
+        assert_eq!(
+            vec![
+                Item::BlockComment(" a\n #| b |# ".into(), span(0, 0, 1, 11)),
+                Item::List(
+                    vec![
+                        Item::BlockComment("".into(), span(1, 12, 1, 16)),
+                        Item::Atom(Atom::Symbol("c".into(), span(1, 16, 1, 17))),
+                    ],
+                    span(1, 11, 1, 18)
+                ),
+            ],
+            Parser::from("#| a\n #| b |# |#(#||#c)").parse().unwrap()
+        );
+
+        assert_eq!(
+            Err(Error::UnterminatedBlockComment(span(0, 0, 0, 14))),
+            Parser::from("#| a #| b |# c").parse()
+        );
+    }
+
+    #[test]
+    fn parse_datum_comment() {
+        assert_eq!(
+            vec![Item::List(
+                vec![
+                    Item::Atom(Atom::Symbol("a".into(), span(0, 1, 0, 2))),
+                    Item::DatumComment(
+                        Box::new(Item::List(
+                            vec![Item::Atom(Atom::Symbol("b".into(), span(1, 1, 1, 2)))],
+                            span(1, 0, 1, 3)
+                        )),
+                        span(0, 3, 1, 3)
+                    ),
+                ],
+                span(0, 0, 1, 4)
+            )],
+            Parser::from("(a #_ ; c\n(b))").parse().unwrap()
+        );
+
+        // Datum comments in front of a datum comment are discarded.
+        assert_eq!(
+            vec![Item::DatumComment(
+                Box::new(Item::Atom(Atom::Symbol("b".into(), span(0, 8, 0, 9)))),
+                span(0, 0, 0, 9)
+            )],
+            Parser::from("#_ #_ a b").parse().unwrap()
+        );
+
+        assert_eq!(
+            Err(Error::DatumCommentWithoutValue(span(0, 3, 0, 5))),
+            Parser::from("(a #_)").parse()
+        );
+    }
+
+    #[test]
+    fn parse_int_valid() {
+        assert_eq!(
+            vec![
+                Item::Atom(Atom::Integer(Integer::from(0), None, span(0, 0, 0, 1))),
+                Item::Atom(Atom::Integer(Integer::from(5), None, span(0, 2, 0, 3))),
+                Item::Atom(Atom::Integer(Integer::from(-25), None, span(0, 4, 0, 7))),
+                Item::Atom(Atom::Integer(
+                    Integer::from(7),
+                    Some("+7".into()),
+                    span(0, 8, 0, 10)
+                )),
+            ],
+            Parser::from("0 5 -25 +7").parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_int_radix() {
+        assert_eq!(
+            vec![
+                Item::Atom(Atom::Integer(
+                    Integer::from(255),
+                    Some("0xFf".into()),
+                    span(0, 0, 0, 4)
+                )),
+                Item::Atom(Atom::Integer(
+                    Integer::from(-8),
+                    Some("-0o10".into()),
+                    span(0, 5, 0, 10)
+                )),
+                Item::Atom(Atom::Integer(
+                    Integer::from(5),
+                    Some("0b1_01".into()),
+                    span(0, 11, 0, 17)
+                )),
+                Item::Atom(Atom::Integer(
+                    Integer::from(1),
+                    Some("0x001".into()),
+                    span(0, 18, 0, 23)
+                )),
+                Item::Atom(Atom::Integer(
+                    Integer::from(1_000_000),
+                    Some("1_000_000".into()),
+                    span(0, 24, 0, 33)
+                )),
+            ],
+            Parser::from("0xFf -0o10 0b1_01 0x001 1_000_000")
+                .parse()
+                .unwrap()
+        );
+
+        assert_eq!(
+            Integer::from(u64::MAX),
+            match &Parser::from("0xffff_ffff_ffff_ffff").parse().unwrap()[0] {
+                Item::Atom(Atom::Integer(v, _, _)) => v.clone(),
+                item => panic!("unexpected item {:?}", item),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_int_radix_invalid() {
+        assert_eq!(
+            Err(Error::IntegerMissingDigits("0x".into(), span(0, 0, 0, 2))),
+            Parser::from("0x").parse()
+        );
+
+        assert_eq!(
+            Err(Error::IntegerParseError("0b102".into(), span(0, 0, 0, 5))),
+            Parser::from("0b102").parse()
+        );
+
+        assert_eq!(
+            Err(Error::IntegerParseError("0x+1".into(), span(0, 0, 0, 4))),
+            Parser::from("0x+1").parse()
+        );
+
+        assert_eq!(
+            Err(Error::IntegerLeadingZero("0_1".into(), span(0, 0, 0, 3))),
+            Parser::from("0_1").parse()
+        );
+
+        for token in &["1_", "0x_1", "1__0", "-0x_1"] {
+            assert_eq!(
+                Err(Error::IntegerMisplacedSeparator(
+                    token.to_string(),
+                    span(0, 0, 0, token.len())
+                )),
+                Parser::from(*token).parse()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_float() {
+        let parse = |s: &str| Parser::from(s).with_options(Options::extended()).parse();
+
+        assert_eq!(
+            vec![
+                Item::Atom(Atom::Float(1.5, None, span(0, 0, 0, 3))),
+                Item::Atom(Atom::Float(-0.25, None, span(0, 4, 0, 9))),
+                Item::Atom(Atom::Float(2e10, Some("2E10".into()), span(0, 10, 0, 14))),
+                Item::Atom(Atom::Float(
+                    1.5e-3,
+                    Some("1.5e-03".into()),
+                    span(0, 15, 0, 22)
+                )),
+                Item::Atom(Atom::Float(0.0, None, span(0, 23, 0, 26))),
+                Item::Atom(Atom::Float(1.5, Some("+1.5".into()), span(0, 27, 0, 31))),
+            ],
+            parse("1.5 -0.25 2E10 1.5e-03 0.0 +1.5").unwrap()
+        );
+
+        for token in &["1.", "01.5", "1e", "1.5e+", "1e999", "1.2.3", "1.5x"] {
+            assert_eq!(
+                Err(Error::FloatParseError(
+                    token.to_string(),
+                    span(0, 0, 0, token.len())
+                )),
+                parse(token)
+            );
+        }
+
+        // Only decimal numbers have a fractional part.
+        assert_eq!(
+            Err(Error::IntegerParseError("0x1.5".into(), span(0, 0, 0, 5))),
+            parse("0x1.5")
+        );
+
+        // Strict LDN has no floating-point numbers.
+        assert_eq!(
+            Err(Error::IntegerParseError("1.5".into(), span(0, 0, 0, 3))),
+            Parser::from("1.5").parse()
+        );
+    }
+
+    #[test]
+    fn parse_ratio() {
+        let parse = |s: &str| Parser::from(s).with_options(Options::extended()).parse();
+
+        assert_eq!(
+            vec![
+                Item::Atom(Atom::Ratio(
+                    Integer::from(3),
+                    Integer::from(4),
+                    span(0, 0, 0, 3)
+                )),
+                Item::Atom(Atom::Ratio(
+                    Integer::from(-6),
+                    Integer::from(8),
+                    span(0, 4, 0, 8)
+                )),
+            ],
+            parse("3/4 -6/8").unwrap()
+        );
+        assert_eq!(
+            vec![Item::Atom(Atom::Ratio(
+                Integer::from(3),
+                Integer::from(4),
+                span(0, 0, 0, 4)
+            ))],
+            parse("+3/4").unwrap()
+        );
+
+        for token in &[
+            "3/", "3/-4", "3/+4", "+03/4", "03/4", "-0/4", "3/4/5", "0x3/4",
+        ] {
+            assert_eq!(
+                Err(Error::RatioParseError(
+                    token.to_string(),
+                    span(0, 0, 0, token.len())
+                )),
+                parse(token)
+            );
+        }
+
+        assert_eq!(
+            Err(Error::RatioZeroDenominator("3/0".into(), span(0, 0, 0, 3))),
+            parse("3/0")
+        );
+
+        // Strict LDN has no ratios.
+        assert_eq!(
+            Err(Error::IntegerParseError("3/4".into(), span(0, 0, 0, 3))),
+            Parser::from("3/4").parse()
+        );
+    }
+
+    #[test]
+    fn parse_collections() {
+        assert_eq!(
+            vec![Item::Vector(
+                vec![
+                    Item::Atom(Atom::Integer(Integer::from(1), None, span(0, 1, 0, 2))),
+                    Item::Map(
+                        vec![
+                            Item::Atom(Atom::Keyword("a".into(), span(0, 4, 0, 6))),
+                            Item::Set(
+                                vec![Item::Atom(Atom::Symbol("b".into(), span(0, 9, 0, 10)))],
+                                span(0, 7, 0, 11)
+                            ),
+                        ],
+                        span(0, 3, 0, 12)
+                    ),
+                    Item::List(vec![], span(0, 13, 0, 15)),
+                ],
+                span(0, 0, 0, 16)
+            )],
+            Parser::from("[1 {:a #{b}} ()]")
+                .with_options(Options::extended())
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_collections_strict() {
+        assert_eq!(
+            Err(Error::InvalidCharacter('[', pos(0, 0))),
+            Parser::from("[1]").parse()
+        );
+
+        assert_eq!(
+            Err(Error::SymbolParseError("a}".into(), span(0, 1, 0, 3))),
+            Parser::from("(a})").parse()
+        );
+    }
+
+    #[test]
+    fn parse_collections_invalid() {
+        let parse = |s: &str| Parser::from(s).with_options(Options::extended()).parse();
+
+        assert_eq!(
+            Err(Error::MismatchedDelimiter(']', pos(0, 2))),
+            parse("(a]")
+        );
+        assert_eq!(Err(Error::UnbalancedParentheses(pos(0, 0))), parse("}"));
+        assert_eq!(Err(Error::UnbalancedParentheses(pos(0, 3))), parse("#{a"));
+        assert_eq!(Err(Error::InvalidCharacter('#', pos(0, 0))), parse("#(a)"));
+
+        assert_eq!(
+            Err(Error::MapOddEntries(span(0, 0, 1, 3))),
+            parse("{:a 1 ; c\n:b}")
+        );
+        assert_eq!(
+            Err(Error::DuplicateKey(span(1, 2, 1, 4))),
+            parse("{0x10 [1 2]\n  16 [1 2]}")
+        );
+        assert_eq!(
+            Err(Error::DuplicateElement(span(0, 14, 0, 25))),
+            parse("#{{:a 1 :b 2} {:b 2 :a 1}}")
+        );
+        assert_eq!(
+            Err(Error::DuplicateElement(span(0, 10, 0, 13))),
+            parse("#{0.0 1e0 1.0 -0.0}")
+        );
+        assert!(parse("#{0.0 -0.0}").is_ok());
+    }
+
+    #[test]
+    fn parse_collections_recovering() {
+        assert_eq!(
+            (
+                vec![
+                    Item::Map(
+                        vec![
+                            Item::Atom(Atom::Keyword("a".into(), span(0, 1, 0, 3))),
+                            Item::Atom(Atom::Keyword("a".into(), span(0, 4, 0, 6))),
+                            Item::Atom(Atom::Keyword("a".into(), span(0, 7, 0, 9))),
+                        ],
+                        span(0, 0, 0, 10)
+                    ),
+                    Item::Vector(
+                        vec![Item::Error("}".into(), span(0, 12, 0, 13))],
+                        span(0, 11, 0, 13)
+                    ),
+                ],
+                vec![
+                    Error::MapOddEntries(span(0, 0, 0, 10)),
+                    Error::MismatchedDelimiter('}', pos(0, 12)),
+                    Error::UnbalancedParentheses(pos(0, 13)),
+                ]
+            ),
+            Parser::from("{:a :a :a} [}")
+                .with_options(Options::extended())
+                .parse_recovering()
+        );
+
+        assert_eq!(
+            vec![Error::DuplicateKey(span(0, 6, 0, 8))],
+            Parser::from("{:a 1 :a 2}")
+                .with_options(Options::extended())
+                .parse_recovering()
+                .1
+        );
+    }
+
+    #[test]
+    fn parse_tagged() {
+        assert_eq!(
+            vec![Item::List(
+                vec![Item::Tagged(
+                    "inst".into(),
+                    Box::new(Item::Atom(Atom::String(
+                        "1985-04-12".into(),
+                        StringStyle::Plain,
+                        span(1, 0, 1, 12)
+                    ))),
+                    span(0, 1, 1, 12)
+                )],
+                span(0, 0, 1, 13)
+            )],
+            Parser::from(
+                "(#inst ; c
+\"1985-04-12\")"
+            )
+            .with_options(Options::extended())
+            .parse()
+            .unwrap()
+        );
+
+        assert_eq!(
+            vec![Item::Tagged(
+                "a".into(),
+                Box::new(Item::Tagged(
+                    "b".into(),
+                    Box::new(Item::Vector(vec![], span(0, 6, 0, 8))),
+                    span(0, 3, 0, 8)
+                )),
+                span(0, 0, 0, 8)
+            )],
+            Parser::from("#a #b []")
+                .with_options(Options::extended())
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_tagged_handlers() {
+        let mut tags = TagRegistry::new();
+        tags.register("neg", |value| match value {
+            Item::Atom(Atom::Integer(v, _, span)) => Ok(Item::Atom(Atom::Integer(
+                Integer::from(-v.as_i64().unwrap()),
+                None,
+                span.clone(),
+            ))),
+            _ => Err("expected an integer".into()),
+        });
+
+        let parse = |s: &str| {
+            Parser::from(s)
+                .with_options(Options::extended())
+                .with_tags(tags.clone())
+                .parse()
+        };
+
+        assert_eq!(
+            Ok(vec![Item::Tagged(
+                "neg".into(),
+                Box::new(Item::Atom(Atom::Integer(
+                    Integer::from(-5),
+                    None,
+                    span(0, 5, 0, 6)
+                ))),
+                span(0, 0, 0, 6)
+            )]),
+            parse("#neg 5")
+        );
+        assert_eq!(
+            Err(Error::InvalidTaggedValue(
+                "neg".into(),
+                "expected an integer".into(),
+                span(0, 3, 0, 7)
+            )),
+            parse("(a #neg b)")
+        );
+    }
+
+    #[test]
+    fn parse_tagged_invalid() {
+        let parse = |s: &str| Parser::from(s).with_options(Options::extended()).parse();
+
+        assert_eq!(Err(Error::TagWithoutValue(span(0, 1, 0, 3))), parse("(#a)"));
+        assert_eq!(
+            Err(Error::TagWithoutValue(span(0, 2, 0, 4))),
+            parse("1 #a ; c")
+        );
+        assert_eq!(
+            Err(Error::SymbolParseError("a\"b".into(), span(0, 0, 0, 4))),
+            parse("#a\"b 1")
+        );
+        assert_eq!(Err(Error::InvalidCharacter('#', pos(0, 0))), parse("#1"));
+
+        assert_eq!(
+            Err(Error::InvalidCharacter('#', pos(0, 0))),
+            Parser::from("#a 1").parse()
+        );
+    }
+
+    #[test]
+    fn parse_tagged_recovering() {
+        let mut tags = TagRegistry::new();
+        tags.register("int", |value| match value {
+            Item::Atom(Atom::Integer(_, _, _)) => Ok(value.clone()),
+            _ => Err("expected an integer".into()),
+        });
+
+        assert_eq!(
+            (
+                vec![Item::List(
+                    vec![Item::Tagged(
+                        "int".into(),
+                        Box::new(Item::Atom(Atom::Keyword("a".into(), span(0, 6, 0, 8)))),
+                        span(0, 1, 0, 8)
+                    )],
+                    span(0, 0, 0, 14)
+                )],
+                vec![
+                    Error::InvalidTaggedValue(
+                        "int".into(),
+                        "expected an integer".into(),
+                        span(0, 1, 0, 5)
+                    ),
+                    Error::TagWithoutValue(span(0, 9, 0, 13)),
+                ]
+            ),
+            Parser::from("(#int :a #int)")
+                .with_options(Options::extended())
+                .with_tags(tags)
+                .parse_recovering()
+        );
+    }
+
+    #[test]
+    fn parse_quotes() {
+        let symbol = |s: &str, start, end| {
+            Box::new(Item::Atom(Atom::Symbol(s.into(), span(0, start, 0, end))))
+        };
+
+        assert_eq!(
+            vec![
+                Item::Quoted(Quote::Quote, symbol("a", 1, 2), span(0, 0, 0, 2)),
+                Item::Quoted(
+                    Quote::Quasiquote,
+                    Box::new(Item::List(
+                        vec![
+                            Item::Quoted(Quote::Unquote, symbol("b", 6, 7), span(0, 5, 0, 7)),
+                            Item::Quoted(
+                                Quote::UnquoteSplicing,
+                                Box::new(Item::Quoted(
+                                    Quote::Quote,
+                                    symbol("c", 12, 13),
+                                    span(0, 11, 0, 13)
+                                )),
+                                span(0, 8, 0, 13)
+                            ),
+                        ],
+                        span(0, 4, 0, 14)
+                    )),
+                    span(0, 3, 0, 14)
+                ),
+            ],
+            Parser::from("'a `(,b ,@ 'c)")
+                .with_options(Options::extended())
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_quotes_invalid() {
+        let parse = |s: &str| Parser::from(s).with_options(Options::extended()).parse();
+
+        assert_eq!(
+            Err(Error::QuoteWithoutValue(span(0, 3, 0, 5))),
+            parse("(a ,@)")
+        );
+        assert_eq!(
+            Err(Error::QuoteWithoutValue(span(0, 0, 0, 1))),
+            parse("' ; c")
+        );
+
+        assert_eq!(
+            Err(Error::InvalidCharacter('\'', pos(0, 0))),
+            Parser::from("'a").parse()
+        );
+    }
+
+    #[test]
+    fn parse_int_big() {
+        let big = "-123456789012345678901234567890";
+
+        assert_eq!(
+            vec![
+                Item::Atom(Atom::Integer(
+                    Integer::from(i64::MAX),
+                    None,
+                    span(0, 0, 0, 19)
+                )),
+                Item::Atom(Atom::Integer(
+                    big.parse().unwrap(),
+                    None,
+                    span(0, 20, 0, 51)
+                )),
+            ],
+            Parser::from(format!("9223372036854775807 {}", big).as_ref())
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_int_invalid() {
+        assert_eq!(
+            Err(Error::IntegerLeadingZero("01".into(), span(0, 0, 0, 2))),
+            Parser::from("01").parse()
+        );
+
+        assert_eq!(
+            Err(Error::IntegerLeadingZero("-0".into(), span(0, 0, 0, 2))),
+            Parser::from("-0").parse()
+        );
+
+        for token in &["-0x0", "-0b0_0", "-0o00"] {
+            assert_eq!(
+                Err(Error::IntegerLeadingZero(
+                    token.to_string(),
+                    span(0, 0, 0, token.len())
+                )),
+                Parser::from(*token).parse()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_string() {
+        assert_eq!(
+            vec![Item::Atom(Atom::String(
+                r#"foo "bar" baz"#.into(),
+                StringStyle::Plain,
+                span(0, 0, 0, 17)
+            ))],
+            Parser::from(r#""foo \"bar\" baz""#).parse().unwrap()
+        );
 
-    /// Returns `true` if `ch` is a printable character.
-    fn is_printable(ch: u8) -> bool {
-        ch >= 0x20 && ch <= 0x7e
+        assert_eq!(
+            Err(Error::InvalidCharacter('\n', pos(0, 4))),
+            Parser::from("\"foo\nbar\"").parse()
+        );
     }
 
-    /// Returns `true` if `ch` is considered a whitespace character according to the grammar.
-    fn is_whitespace(ch: u8) -> bool {
-        ch == b' ' || ch == b'\n'
-    }
+    #[test]
+    fn parse_string_escapes() {
+        assert_eq!(
+            vec![Item::Atom(Atom::String(
+                "a\\b\"c\nd\re\tf\u{e8}\u{1F600}".into(),
+                StringStyle::Plain,
+                span(0, 0, 0, 33)
+            ))],
+            Parser::from(r#""a\\b\"c\nd\re\tf\u{e8}\u{1F600}""#)
+                .parse()
+                .unwrap()
+        );
 
-    /// Returns `true` if `ch` is a digit between 1 and 9.
-    fn is_digit_1_9(ch: u8) -> bool {
-        ch >= b'1' && ch <= b'9'
-    }
+        assert_eq!(
+            Err(Error::InvalidEscape("\\q".into(), span(0, 2, 0, 4))),
+            Parser::from(r#""a\qb""#).parse()
+        );
 
-    /// Returns `true` if `ch` is a symbol constituent.
-    fn is_symbol(ch: u8) -> bool {
-        match ch {
-            b'+' | b'-' | b'*' | b'/' | b'%' | b'=' | b'<' | b'>' | b'?' | b'!' => true,
-            _ => Self::is_alpha(ch),
-        }
-    }
+        assert_eq!(
+            Err(Error::InvalidEscape(
+                "\\u{110000}".into(),
+                span(0, 1, 0, 11)
+            )),
+            Parser::from(r#""\u{110000}""#).parse()
+        );
 
-    /// Returns `true` if `ch` is an alphabetic character (`a` to `z` either lowercase or
-    /// uppercase).
-    fn is_alpha(ch: u8) -> bool {
-        ch >= b'a' && ch <= b'z' || ch >= b'A' && ch <= b'Z'
-    }
-}
+        assert_eq!(
+            Err(Error::InvalidEscape(
+                "\\u{1234567".into(),
+                span(0, 1, 0, 11)
+            )),
+            Parser::from(r#""\u{1234567}""#).parse()
+        );
 
-impl<'a> From<&'a str> for Parser<str::Bytes<'a>> {
-    fn from(v: &'a str) -> Self {
-        Self::new(v.bytes())
+        assert_eq!(
+            Err(Error::InvalidEscape("\\u0".into(), span(0, 1, 0, 4))),
+            Parser::from(r#""\u00e8""#).parse()
+        );
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use super::super::pos::{pos, span};
 
     #[test]
-    fn parse_empty() {
-        assert!(Parser::from("").parse().unwrap().is_empty());
-    }
+    fn parse_string_unterminated() {
+        assert_eq!(
+            Err(Error::UnterminatedString(span(0, 0, 0, 4))),
+            Parser::from(r#""foo"#).parse()
+        );
 
-    #[test]
-    fn parse_unknown() {
         assert_eq!(
-            Err(Error::InvalidCharacter(b'\r', pos(0, 1))),
-            Parser::from(" \r").parse()
+            Err(Error::InvalidEscape("\\".into(), span(0, 4, 0, 5))),
+            Parser::from(r#""foo\"#).parse()
         );
     }
 
     #[test]
-    fn parse_comment() {
+    fn parse_raw_string() {
         assert_eq!(
             vec![
-                Item::Comment("foo bar".into(), span(0, 2, 0, 11)),
-                Item::Comment("baz".into(), span(1, 0, 1, 5)),
-                Item::Comment("qux".into(), span(2, 0, 2, 6)),
-                Item::Comment("; quux ;".into(), span(3, 0, 3, 11))
+                Item::Atom(Atom::String(
+                    r"a\b".into(),
+                    StringStyle::Raw(1),
+                    span(0, 0, 0, 7)
+                )),
+                Item::Atom(Atom::String(
+                    "\"#\n\"".into(),
+                    StringStyle::Raw(2),
+                    span(0, 8, 1, 4)
+                )),
             ],
-            Parser::from("  ; foo bar\n; baz\n;; qux\n;; ; quux ;")
+            Parser::from("#\"a\\b\"# ##\"\"#\r\n\"\"##")
                 .parse()
                 .unwrap()
-        )
-    }
+        );
 
-    #[test]
-    fn parse_int_valid() {
         assert_eq!(
-            vec![
-                Item::Atom(Atom::Integer(0, span(0, 0, 0, 1))),
-                Item::Atom(Atom::Integer(5, span(0, 2, 0, 3))),
-                Item::Atom(Atom::Integer(-25, span(0, 4, 0, 7))),
-            ],
-            Parser::from("0 5 -25").parse().unwrap()
+            Err(Error::UnterminatedString(span(0, 0, 0, 6))),
+            Parser::from("##\"a\"#").parse()
+        );
+
+        assert_eq!(
+            Err(Error::InvalidCharacter('a', pos(0, 2))),
+            Parser::from("##a").parse()
         );
     }
 
     #[test]
-    fn parse_int_invalid() {
+    fn parse_text_block() {
         assert_eq!(
-            Err(Error::IntegerLeadingZero("01".into(), span(0, 0, 0, 2))),
-            Parser::from("01").parse()
+            vec![Item::List(
+                vec![Item::Atom(Atom::String(
+                    "a \"\"b\"\n\n  c\\\n".into(),
+                    StringStyle::Block,
+                    span(0, 1, 4, 7)
+                ))],
+                span(0, 0, 4, 8)
+            )],
+            Parser::from("(\"\"\"\n    a \"\"b\"  \n\n      c\\\\\n    \"\"\")")
+                .parse()
+                .unwrap()
         );
 
+        // The closing delimiter doesn't need to be on its own line, and escaped whitespace is
+        // kept.
         assert_eq!(
-            Err(Error::IntegerLeadingZero("-0".into(), span(0, 0, 0, 2))),
-            Parser::from("-0").parse()
+            vec![Item::Atom(Atom::String(
+                " a\n\tb\t".into(),
+                StringStyle::Block,
+                span(0, 0, 2, 10)
+            ))],
+            Parser::from("\"\"\"\r\n  \\u{20}a\r\n  \\tb\\t\"\"\"")
+                .parse()
+                .unwrap()
         );
-    }
 
-    #[test]
-    fn parse_string() {
         assert_eq!(
             vec![Item::Atom(Atom::String(
-                r#"foo "bar" baz"#.into(),
-                span(0, 0, 0, 16)
+                "".into(),
+                StringStyle::Plain,
+                span(0, 0, 0, 2)
             ))],
-            Parser::from(r#""foo \"bar\" baz""#).parse().unwrap()
+            Parser::from("\"\"").parse().unwrap()
         );
 
         assert_eq!(
-            Err(Error::InvalidCharacter(b'\n', pos(0, 4))),
-            Parser::from("\"foo\nbar\"").parse()
+            Err(Error::InvalidCharacter('a', pos(0, 3))),
+            Parser::from("\"\"\"a\n\"\"\"").parse()
+        );
+
+        assert_eq!(
+            Err(Error::UnterminatedString(span(0, 0, 1, 4))),
+            Parser::from("\"\"\"\na \"\"").parse()
         );
     }
 
@@ -335,6 +2211,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_literals() {
+        assert_eq!(
+            vec![
+                Item::Atom(Atom::Bool(true, span(0, 0, 0, 4))),
+                Item::Atom(Atom::Bool(false, span(0, 5, 0, 10))),
+                Item::Atom(Atom::Nil(span(0, 11, 0, 14))),
+                Item::Atom(Atom::Keyword("nil".into(), span(0, 15, 0, 19))),
+                Item::Atom(Atom::Symbol("true?".into(), span(0, 20, 0, 25))),
+            ],
+            Parser::from("true false nil :nil true?").parse().unwrap()
+        );
+
+        let options = Options {
+            literals_as_symbols: true,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            vec![Item::Atom(Atom::Symbol("nil".into(), span(0, 0, 0, 3)))],
+            Parser::from("nil").with_options(options).parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_char() {
+        assert_eq!(
+            vec![Item::List(
+                vec![
+                    Item::Atom(Atom::Char('a', span(0, 1, 0, 3))),
+                    Item::Atom(Atom::Char('\n', span(0, 4, 0, 12))),
+                    Item::Atom(Atom::Char('è', span(0, 13, 0, 15))),
+                    Item::Atom(Atom::Char('\u{1f600}', span(0, 16, 0, 25))),
+                    Item::Atom(Atom::Char(')', span(0, 26, 0, 28))),
+                ],
+                span(0, 0, 0, 29)
+            )],
+            Parser::from("(\\a \\newline \\è \\u{1f600} \\))")
+                .parse()
+                .unwrap()
+        );
+
+        let parse = |s: &str| Parser::from(s).parse();
+
+        assert_eq!(
+            Err(Error::CharParseError("\\ab".into(), span(0, 0, 0, 3))),
+            parse("\\ab")
+        );
+        assert_eq!(
+            Err(Error::CharParseError("\\u{d800}".into(), span(0, 0, 0, 8))),
+            parse("\\u{d800}")
+        );
+        assert_eq!(
+            Err(Error::CharParseError("\\".into(), span(0, 0, 0, 1))),
+            parse("\\ a")
+        );
+
+        // The closing brace of a Unicode escape doesn't end the token in collections.
+        let parse = |s: &str| Parser::from(s).with_options(Options::extended()).parse();
+
+        assert_eq!(
+            Ok(vec![Item::Map(
+                vec![
+                    Item::Atom(Atom::Char('A', span(0, 1, 0, 7))),
+                    Item::Atom(Atom::Char('}', span(0, 8, 0, 10))),
+                ],
+                span(0, 0, 0, 11)
+            )]),
+            parse("{\\u{41} \\}}")
+        );
+        assert_eq!(
+            Err(Error::CharParseError("\\u{41".into(), span(0, 1, 0, 6))),
+            parse("[\\u{41]")
+        );
+    }
+
     #[test]
     fn parse_symbol_valid() {
         assert_eq!(
@@ -350,13 +2302,85 @@ mod tests {
             vec![Item::Atom(Atom::Symbol("-".into(), span(0, 0, 0, 1)))],
             Parser::from("-").parse().unwrap()
         );
+
+        for token in &[
+            "vec3", "utf-8", "x2", "a.b", "_", "&rest", "$x", "x'", "-x", "+", ".",
+        ] {
+            assert_eq!(
+                vec![Item::Atom(Atom::Symbol(
+                    token.to_string(),
+                    span(0, 0, 0, token.len())
+                ))],
+                Parser::from(*token).parse().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_qualified() {
+        let items = Parser::from("http/get :user/id / :id").parse().unwrap();
+        let parts = items
+            .iter()
+            .map(|item| match item {
+                Item::Atom(atom) => (atom.namespace(), atom.name()),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (Some("http"), Some("get")),
+                (Some("user"), Some("id")),
+                (None, Some("/")),
+                (None, Some("id")),
+            ],
+            parts
+        );
+    }
+
+    #[test]
+    fn parse_qualified_invalid() {
+        for (token, start) in &[
+            ("a/", 0),
+            ("a/b/c", 0),
+            ("//", 0),
+            ("/a", 0),
+            (":a/", 1),
+            (":/", 1),
+        ] {
+            assert_eq!(
+                Err(Error::SymbolParseError(
+                    token[*start..].into(),
+                    span(0, *start, 0, token.len())
+                )),
+                Parser::from(*token).parse()
+            );
+        }
     }
 
     #[test]
     fn parse_symbol_invalid() {
         assert_eq!(
-            Err(Error::SymbolParseError("a$b".into(), span(0, 0, 0, 3))),
-            Parser::from("a$b").parse()
+            Err(Error::SymbolParseError("a@b".into(), span(0, 0, 0, 3))),
+            Parser::from("a@b").parse()
+        );
+
+        // A sign or a dot followed by a digit starts a number, even after a namespace.
+        assert_eq!(
+            Err(Error::IntegerParseError("+1x".into(), span(0, 0, 0, 3))),
+            Parser::from("+1x").parse()
+        );
+        assert_eq!(
+            Err(Error::IntegerParseError(".5".into(), span(0, 0, 0, 2))),
+            Parser::from(".5").parse()
+        );
+        assert_eq!(
+            Err(Error::SymbolParseError("a/-1".into(), span(0, 0, 0, 4))),
+            Parser::from("a/-1").parse()
+        );
+        assert_eq!(
+            Err(Error::SymbolParseError("1a".into(), span(0, 1, 0, 3))),
+            Parser::from(":1a").parse()
         );
     }
 
@@ -373,17 +2397,21 @@ mod tests {
         assert_eq!(
             vec![Item::List(
                 vec![
-                    Item::Atom(Atom::Integer(1, span(0, 1, 0, 2))),
+                    Item::Atom(Atom::Integer(Integer::from(1), None, span(0, 1, 0, 2))),
                     Item::List(
                         vec![
-                            Item::Atom(Atom::Integer(2, span(0, 4, 0, 5))),
-                            Item::Atom(Atom::Integer(3, span(0, 6, 0, 7))),
+                            Item::Atom(Atom::Integer(Integer::from(2), None, span(0, 4, 0, 5))),
+                            Item::Atom(Atom::Integer(Integer::from(3), None, span(0, 6, 0, 7))),
                         ],
                         span(0, 3, 0, 8)
                     ),
-                    Item::Atom(Atom::Integer(4, span(0, 9, 0, 10))),
+                    Item::Atom(Atom::Integer(Integer::from(4), None, span(0, 9, 0, 10))),
                     Item::List(
-                        vec![Item::Atom(Atom::String("foo".into(), span(0, 12, 0, 16)))],
+                        vec![Item::Atom(Atom::String(
+                            "foo".into(),
+                            StringStyle::Plain,
+                            span(0, 12, 0, 17)
+                        ))],
                         span(0, 11, 0, 18)
                     ),
                 ],
@@ -400,4 +2428,256 @@ mod tests {
             Parser::from("(1 (2) 3").parse()
         );
     }
+
+    #[test]
+    fn parse_list_unbalanced_closing() {
+        assert_eq!(
+            Err(Error::UnbalancedParentheses(pos(0, 2))),
+            Parser::from("a ) b").parse()
+        );
+    }
+
+    #[test]
+    fn parse_recovering_atoms() {
+        assert_eq!(
+            (
+                vec![
+                    Item::List(
+                        vec![
+                            Item::Atom(Atom::Symbol("a".into(), span(0, 1, 0, 2))),
+                            Item::Error("01".into(), span(0, 3, 0, 5)),
+                            Item::Atom(Atom::Symbol("b".into(), span(0, 6, 0, 7))),
+                        ],
+                        span(0, 0, 0, 8)
+                    ),
+                    Item::Error("c@d".into(), span(0, 9, 0, 12)),
+                    Item::Error("\u{7}".into(), span(0, 13, 0, 14)),
+                    Item::Atom(Atom::Symbol("e".into(), span(0, 15, 0, 16))),
+                ],
+                vec![
+                    Error::IntegerLeadingZero("01".into(), span(0, 3, 0, 5)),
+                    Error::SymbolParseError("c@d".into(), span(0, 9, 0, 12)),
+                    Error::InvalidCharacter('\u{7}', pos(0, 13)),
+                ]
+            ),
+            Parser::from("(a 01 b) c@d \u{7} e").parse_recovering()
+        );
+    }
+
+    #[test]
+    fn parse_recovering_string() {
+        assert_eq!(
+            (
+                vec![
+                    Item::Error("\"foo".into(), span(0, 0, 0, 4)),
+                    Item::Atom(Atom::Symbol("x".into(), span(1, 0, 1, 1))),
+                ],
+                vec![Error::InvalidCharacter('\n', pos(0, 4))]
+            ),
+            Parser::from("\"foo\nx").parse_recovering()
+        );
+    }
+
+    #[test]
+    fn parse_recovering_unbalanced() {
+        assert_eq!(
+            (
+                vec![Item::List(
+                    vec![
+                        Item::Atom(Atom::Symbol("a".into(), span(0, 1, 0, 2))),
+                        Item::List(
+                            vec![Item::Atom(Atom::Symbol("b".into(), span(0, 4, 0, 5)))],
+                            span(0, 3, 0, 5)
+                        ),
+                    ],
+                    span(0, 0, 0, 5)
+                )],
+                vec![
+                    Error::UnbalancedParentheses(pos(0, 5)),
+                    Error::UnbalancedParentheses(pos(0, 5)),
+                ]
+            ),
+            Parser::from("(a (b").parse_recovering()
+        );
+
+        assert_eq!(
+            (
+                vec![
+                    Item::Atom(Atom::Symbol("a".into(), span(0, 0, 0, 1))),
+                    Item::Error(")".into(), span(0, 2, 0, 3)),
+                    Item::Atom(Atom::Symbol("b".into(), span(0, 4, 0, 5))),
+                ],
+                vec![Error::UnbalancedParentheses(pos(0, 2))]
+            ),
+            Parser::from("a ) b").parse_recovering()
+        );
+    }
+
+    #[test]
+    fn iterate() {
+        let mut parser = Parser::from("(a) ; b\n01 c");
+
+        assert_eq!(
+            Some(Ok(Item::List(
+                vec![Item::Atom(Atom::Symbol("a".into(), span(0, 1, 0, 2)))],
+                span(0, 0, 0, 3)
+            ))),
+            parser.next()
+        );
+        assert_eq!(
+            Some(Ok(Item::Comment("b".into(), span(0, 4, 0, 7)))),
+            parser.next()
+        );
+        assert_eq!(
+            Some(Err(Error::IntegerLeadingZero(
+                "01".into(),
+                span(1, 0, 1, 2)
+            ))),
+            parser.next()
+        );
+        assert_eq!(None, parser.next());
+    }
+
+    #[test]
+    fn iterate_lazily() {
+        // The stream never ends, so this only works if items are parsed on demand.
+        let items = Parser::new(b"(record 1) ".iter().cloned().cycle())
+            .take(3)
+            .collect::<Result<List>>()
+            .unwrap();
+
+        assert_eq!(3, items.len());
+        assert_eq!(&span(0, 22, 0, 32), items[2].span());
+    }
+
+    /// A reader returning an error after producing all of its data.
+    struct FailingReader<'a>(&'a [u8]);
+
+    impl<'a> io::Read for FailingReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("boom"));
+            }
+
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn parse_utf8() {
+        assert_eq!(
+            vec![
+                Item::Comment("Grüße".into(), span(0, 0, 0, 7)),
+                Item::List(
+                    vec![
+                        Item::Atom(Atom::Symbol("città".into(), span(1, 1, 1, 6))),
+                        Item::Atom(Atom::String(
+                            "日本語".into(),
+                            StringStyle::Plain,
+                            span(1, 7, 1, 12)
+                        )),
+                        Item::Atom(Atom::Keyword("ñ".into(), span(1, 13, 1, 15))),
+                    ],
+                    span(1, 0, 1, 16)
+                ),
+            ],
+            Parser::from("; Grüße\n(città \"日本語\" :ñ)")
+                .parse()
+                .unwrap()
+        );
+
+        // Errors report whole characters, at positions counted in characters.
+        assert_eq!(
+            Err(Error::InvalidCharacter('€', pos(0, 4))),
+            Parser::from("(è) €").parse()
+        );
+
+        assert_eq!(
+            Err(Error::SymbolParseError("a😀".into(), span(0, 0, 0, 2))),
+            Parser::from("a😀").parse()
+        );
+    }
+
+    #[test]
+    fn parse_utf8_malformed() {
+        assert_eq!(
+            Err(Error::Utf8Error(span(0, 3, 0, 4))),
+            Parser::new(b"\"ab\xff\"".iter().cloned()).parse()
+        );
+
+        assert_eq!(
+            Err(Error::Utf8Error(span(0, 1, 0, 2))),
+            Parser::new(b"a\xc3".iter().cloned()).parse()
+        );
+    }
+
+    #[test]
+    fn parse_reader() {
+        assert_eq!(
+            Ok(vec![Item::List(
+                vec![Item::Atom(Atom::Integer(
+                    Integer::from(1),
+                    None,
+                    span(0, 1, 0, 2)
+                ))],
+                span(0, 0, 0, 3)
+            )]),
+            Parser::from_reader(&b"(1)"[..]).parse()
+        );
+    }
+
+    #[test]
+    fn parse_reader_error() {
+        assert_eq!(
+            Err(Error::Io(io::ErrorKind::Other, "boom".into(), pos(1, 2))),
+            Parser::from_reader(FailingReader(b"(a\n b")).parse()
+        );
+
+        assert_eq!(
+            vec![
+                Error::Io(io::ErrorKind::Other, "boom".into(), pos(0, 4)),
+                Error::UnbalancedParentheses(pos(0, 4)),
+            ],
+            Parser::from_reader(FailingReader(b"(a b"))
+                .parse_recovering()
+                .1
+        );
+    }
+
+    #[test]
+    fn parse_reader_error_mid_token() {
+        let boom = |pos| Error::Io(io::ErrorKind::Other, "boom".into(), pos);
+
+        assert_eq!(
+            Err(boom(pos(0, 4))),
+            Parser::from_reader(FailingReader(b"\"abc")).parse()
+        );
+        assert_eq!(
+            Err(boom(pos(0, 7))),
+            Parser::from_reader(FailingReader(b"(a #| x")).parse()
+        );
+        assert_eq!(
+            Err(boom(pos(0, 2))),
+            Parser::from_reader(FailingReader(b"\"a\xc3")).parse()
+        );
+        assert_eq!(
+            vec![
+                Ok(Item::Atom(Atom::Integer(
+                    Integer::from(1),
+                    None,
+                    span(0, 0, 0, 1)
+                ))),
+                Err(boom(pos(0, 5))),
+            ],
+            Parser::from_reader(FailingReader(b"1 123")).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            (
+                vec![Item::Error("\"abc".into(), span(0, 0, 0, 4))],
+                vec![boom(pos(0, 4))]
+            ),
+            Parser::from_reader(FailingReader(b"\"abc")).parse_recovering()
+        );
+    }
 }
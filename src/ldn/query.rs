@@ -0,0 +1,389 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Selects items out of a document with queries written in LDN.
+//!
+//! A query is a sequence of steps, each selecting items out of the ones selected by the previous
+//! step, starting from the document itself. Steps are:
+//!
+//! - An integer `n`: the `n`th element of lists, vectors and sets, counting from zero. Negative
+//!   integers count from the end, so `-1` is the last element.
+//! - A keyword `:k`: the value of the key `:k` in maps, or the item following `:k` in lists and
+//!   vectors used as property lists (`(server :port 80)`).
+//! - A symbol `s`: the elements which are lists starting with the symbol `s`.
+//! - `*`: all the elements of lists, vectors and sets, the values of maps and the values of
+//!   quoted and tagged items.
+//! - `**`: the item itself and, recursively, all the items selected by `*`.
+//! - `(key k)`: like a keyword, with any key `k`, as in `(key "name")`.
+//! - `(head s)`: like a symbol, for the symbols that have a meaning of their own, like `(head *)`.
+//!
+//! For instance, `** server :port` selects the ports of all the `(server :port 80)` lists in the
+//! document, while `config :servers 0 :name` selects `"a"` out of
+//! `(config :servers [{:name "a"}])`. Comments are never selected.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::ast::{Atom, Item};
+use super::error::Error;
+use super::options::Options;
+use super::parser::Parser;
+use super::pos::Span;
+
+/// A parsed query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    steps: Vec<Step>,
+}
+
+impl Query {
+    /// Returns the items selected by the query out of a document, in the order they are found by
+    /// each step. A query without steps selects the top-level items. The span of each item
+    /// locates it in the document.
+    pub fn select<'a>(&self, items: &'a [Item]) -> Vec<&'a Item> {
+        if self.steps.is_empty() {
+            return values(items);
+        }
+
+        let mut nodes = vec![Node::Document(items)];
+
+        for step in &self.steps {
+            nodes = nodes
+                .into_iter()
+                .flat_map(|node| step.apply(node))
+                .collect();
+        }
+
+        // The document itself is only kept by `**`, which also selects all its items.
+        nodes
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Document(_) => None,
+                Node::Item(item) => Some(item),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseQueryError;
+
+    /// Parses a query from LDN text, with all the extensions enabled.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let items = Parser::from(s)
+            .with_options(Options::extended())
+            .parse()
+            .map_err(ParseQueryError::Parse)?;
+        let steps = values(&items)
+            .into_iter()
+            .map(Step::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Query { steps })
+    }
+}
+
+/// The error returned when a string doesn't represent a valid query.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseQueryError {
+    /// The query isn't valid LDN.
+    Parse(Error),
+    /// An item that isn't a valid step of a query.
+    InvalidStep(Span),
+}
+
+impl fmt::Display for ParseQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseQueryError::Parse(err) => err.fmt(f),
+            ParseQueryError::InvalidStep(span) => write!(f, "{} invalid query step", span),
+        }
+    }
+}
+
+impl error::Error for ParseQueryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseQueryError::Parse(err) => Some(err),
+            ParseQueryError::InvalidStep(_) => None,
+        }
+    }
+}
+
+//
+// Private
+//
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Index(i64),
+    Key(Item),
+    Head(String),
+    Children,
+    Descendants,
+}
+
+impl Step {
+    fn parse(item: &Item) -> Result<Step, ParseQueryError> {
+        let invalid = || ParseQueryError::InvalidStep(item.span().clone());
+
+        match item {
+            Item::Atom(Atom::Integer(v, _, _)) => v.as_i64().map(Step::Index).ok_or_else(invalid),
+            Item::Atom(Atom::Keyword(_, _)) => Ok(Step::Key(item.clone())),
+            Item::Atom(Atom::Symbol(v, _)) if v == "*" => Ok(Step::Children),
+            Item::Atom(Atom::Symbol(v, _)) if v == "**" => Ok(Step::Descendants),
+            Item::Atom(Atom::Symbol(v, _)) => Ok(Step::Head(v.clone())),
+            Item::List(items, _) => match values(items).as_slice() {
+                [head, key] if head_symbol(head) == Some("key") => Ok(Step::Key((*key).clone())),
+                [head, Item::Atom(Atom::Symbol(v, _))] if head_symbol(head) == Some("head") => {
+                    Ok(Step::Head(v.clone()))
+                }
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+
+    fn apply<'a>(&self, node: Node<'a>) -> Vec<Node<'a>> {
+        let items = match self {
+            Step::Index(index) => {
+                let elements = match node {
+                    Node::Document(items) => values(items),
+                    Node::Item(Item::List(items, _))
+                    | Node::Item(Item::Set(items, _))
+                    | Node::Item(Item::Vector(items, _)) => values(items),
+                    _ => vec![],
+                };
+                let index = if *index < 0 {
+                    elements.len() as i64 + index
+                } else {
+                    *index
+                };
+
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| elements.get(index).copied())
+                    .into_iter()
+                    .collect()
+            }
+            Step::Key(key) => match node {
+                Node::Item(Item::Map(items, _)) => values(items)
+                    .chunks(2)
+                    .filter(|entry| entry.len() == 2 && entry[0].eq_value(key))
+                    .map(|entry| entry[1])
+                    .collect(),
+                Node::Document(items) => property(items, key),
+                Node::Item(Item::List(items, _)) | Node::Item(Item::Vector(items, _)) => {
+                    property(items, key)
+                }
+                _ => vec![],
+            },
+            Step::Head(symbol) => children(node)
+                .into_iter()
+                .filter(|item| match item {
+                    Item::List(items, _) => {
+                        values(items).first().and_then(|head| head_symbol(head))
+                            == Some(symbol.as_str())
+                    }
+                    _ => false,
+                })
+                .collect(),
+            Step::Children => children(node),
+            Step::Descendants => {
+                let mut ret = vec![node];
+                let mut pending = children(node);
+
+                // Visits the descendants depth-first, pushing the children of each item in reverse
+                // so that they're popped, and selected, in document order.
+                pending.reverse();
+
+                while let Some(item) = pending.pop() {
+                    ret.push(Node::Item(item));
+                    pending.extend(children(Node::Item(item)).into_iter().rev());
+                }
+
+                return ret;
+            }
+        };
+
+        items.into_iter().map(Node::Item).collect()
+    }
+}
+
+/// An item a step is applied to. The document behaves like a list of its top-level items.
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Document(&'a [Item]),
+    Item(&'a Item),
+}
+
+/// Returns the items selected by `*`.
+fn children<'a>(node: Node<'a>) -> Vec<&'a Item> {
+    match node {
+        Node::Document(items) => values(items),
+        Node::Item(Item::List(items, _))
+        | Node::Item(Item::Set(items, _))
+        | Node::Item(Item::Vector(items, _)) => values(items),
+        Node::Item(Item::Map(items, _)) => values(items).into_iter().skip(1).step_by(2).collect(),
+        Node::Item(Item::Quoted(_, value, _)) | Node::Item(Item::Tagged(_, value, _)) => {
+            vec![value]
+        }
+        Node::Item(_) => vec![],
+    }
+}
+
+/// Returns the items following `key` in a property list.
+fn property<'a>(items: &'a [Item], key: &Item) -> Vec<&'a Item> {
+    values(items)
+        .windows(2)
+        .filter(|pair| pair[0].eq_value(key))
+        .map(|pair| pair[1])
+        .collect()
+}
+
+/// Returns the name of a symbol, or `None` for any other item.
+fn head_symbol(item: &Item) -> Option<&str> {
+    match item {
+        Item::Atom(Atom::Symbol(v, _)) => Some(v),
+        _ => None,
+    }
+}
+
+/// Returns the items that are not comments.
+fn values(items: &[Item]) -> Vec<&Item> {
+    items.iter().filter(|item| !item.is_comment()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::pos::span;
+
+    const DOCUMENT: &str = r#"
+        (server :name "a" :port 80 :tags #{:web})
+        ; (server :name "b")
+        (server :name "b" :port 81 :routes [(route "/") (route "/api")])
+        {:servers [{:name "c" :port 82} {"name" "d"}] :* 'x}
+    "#;
+
+    fn select(query: &str) -> Vec<String> {
+        let items = Parser::from(DOCUMENT)
+            .with_options(Options::extended())
+            .parse()
+            .unwrap();
+
+        query
+            .parse::<Query>()
+            .unwrap()
+            .select(&items)
+            .iter()
+            .map(|item| item.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Ok(Query {
+                steps: vec![
+                    Step::Index(-1),
+                    Step::Key(Item::Atom(Atom::Keyword("a".into(), span(0, 3, 0, 5)))),
+                    Step::Head("b".into()),
+                    Step::Children,
+                    Step::Descendants,
+                    Step::Head("*".into()),
+                ]
+            }),
+            "-1 :a b * ** ; comment\n (head *)".parse::<Query>()
+        );
+
+        assert_eq!(
+            Err(ParseQueryError::InvalidStep(span(0, 2, 0, 7))),
+            "a \"str\"".parse::<Query>()
+        );
+        assert_eq!(
+            Err(ParseQueryError::InvalidStep(span(0, 0, 0, 9))),
+            "(head :a)".parse::<Query>()
+        );
+        assert_eq!(
+            Err(ParseQueryError::InvalidStep(span(0, 0, 0, 9))),
+            "(key a b)".parse::<Query>()
+        );
+        assert_eq!(
+            Err(ParseQueryError::Parse(Error::UnterminatedString(span(
+                0, 0, 0, 1
+            )))),
+            "\"".parse::<Query>()
+        );
+    }
+
+    #[test]
+    fn select_steps() {
+        assert_eq!(3, select("").len());
+        assert_eq!(vec!["80", "81"], select("server :port"));
+        assert_eq!(vec!["(route \"/api\")"], select("1 :routes -1"));
+        assert_eq!(vec!["\"c\""], select("2 :servers 0 :name"));
+        assert_eq!(vec!["\"d\""], select("2 :servers * (key \"name\")"));
+        assert_eq!(vec!["'x"], select("2 (key :*)"));
+        assert_eq!(vec![":web"], select("0 :tags *"));
+        assert_eq!(Vec::<String>::new(), select("0 :tags 5"));
+        assert_eq!(Vec::<String>::new(), select("3"));
+        assert_eq!(
+            vec!["{:name \"c\" :port 82}", "{\"name\" \"d\"}", "x"],
+            select("2 * *")
+        );
+    }
+
+    #[test]
+    fn select_descendants() {
+        assert_eq!(vec!["80", "81", "82"], select("** :port"));
+        assert_eq!(vec!["\"a\"", "\"b\""], select("** server :name"));
+        assert_eq!(
+            vec!["(route \"/\")", "(route \"/api\")"],
+            select("** route")
+        );
+        assert_eq!(vec!["\"/\"", "\"/api\""], select("** route 1"));
+        assert_eq!(
+            vec![
+                "[{:name \"c\" :port 82} {\"name\" \"d\"}]",
+                "{:name \"c\" :port 82}",
+                "\"c\"",
+                "82",
+                "{\"name\" \"d\"}",
+                "\"d\"",
+            ],
+            select("2 :servers **")
+        );
+    }
+
+    #[test]
+    fn select_spans() {
+        let items = Parser::from("(a :b\n  (c 1))").parse().unwrap();
+        let query = ":b".parse::<Query>().unwrap();
+
+        assert_eq!(Vec::<&Item>::new(), query.select(&items));
+
+        let query = "0 :b".parse::<Query>().unwrap();
+        let selected = query.select(&items);
+
+        assert_eq!(
+            vec![&span(1, 2, 1, 7)],
+            selected.iter().map(|item| item.span()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,432 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Serializes Rust values into LDN items with serde.
+//!
+//! Values are mapped to items as follows:
+//!
+//! - Booleans, integers, floating-point numbers, characters and strings become the matching atoms.
+//!   Floating-point numbers must be finite, and need the `floats_and_ratios` extension to be read
+//!   back.
+//! - `None`, `()` and unit structs become `nil`, while `Some(v)` becomes `v` itself. Like in JSON,
+//!   `Some(None)` and `Some(())` can't be told apart from `None`.
+//! - Sequences, tuples, tuple structs and bytes become lists: `(1 2 3)`.
+//! - Structs become property lists of keywords and values: `(:name "a" :port 80)`.
+//! - Maps become maps, which need the `collections` extension to be read back: `{"a" 1}`.
+//! - Unit variants become symbols: `Red`. Other variants become lists starting with the name of the
+//!   variant: `(Rgb 0 0 255)`, `(Circle :radius 1.5)`.
+
+use std::error;
+use std::fmt;
+use std::result;
+
+use serde::ser::{self, Serialize};
+
+use super::ast::{Atom, Item, List, StringStyle};
+use super::fmt::fmt;
+use super::integer::Integer;
+use super::parser::is_qualified_symbol;
+use super::pos::Span;
+
+/// A specialized `Result` type for serialization.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The error type for serialization, holding a message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl error::Error for Error {}
+
+/// Serializes a value into an item.
+pub fn to_item<T>(value: &T) -> Result<Item>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(Serializer)
+}
+
+/// Serializes a value into a formatted LDN document.
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    Ok(fmt(&[to_item(value)?]))
+}
+
+/// A serializer producing items, with empty spans.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Item;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeList;
+    type SerializeStructVariant = SerializeList;
+
+    fn serialize_bool(self, v: bool) -> Result<Item> {
+        Ok(atom(Atom::Bool(v, Span::default())))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Item> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Item> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Item> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Item> {
+        Ok(integer(Integer::from(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Item> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Item> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Item> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Item> {
+        Ok(integer(Integer::from(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Item> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Item> {
+        if !v.is_finite() {
+            return Err(Error(format!("cannot serialize {} as LDN", v)));
+        }
+
+        Ok(atom(Atom::Float(v, None, Span::default())))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Item> {
+        Ok(atom(Atom::Char(v, Span::default())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Item> {
+        Ok(atom(Atom::String(
+            v.into(),
+            StringStyle::Plain,
+            Span::default(),
+        )))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Item> {
+        Ok(list(
+            v.iter()
+                .map(|&b| integer(Integer::from(i64::from(b))))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Item> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Item>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Item> {
+        Ok(atom(Atom::Nil(Span::default())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Item> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Item> {
+        symbol(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Item>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Item>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(list(vec![symbol(variant)?, to_item(value)?]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeList> {
+        Ok(SerializeList {
+            items: vec![symbol(variant)?],
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+        Ok(SerializeMap {
+            items: Vec::with_capacity(len.unwrap_or(0) * 2),
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len * 2))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeList> {
+        self.serialize_tuple_variant(name, variant_index, variant, len * 2)
+    }
+}
+
+/// Serializes sequences, tuples, structs and variants into lists.
+pub struct SerializeList {
+    items: List,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(to_item(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Item> {
+        Ok(list(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Item> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Item> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Item> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeStruct for SerializeList {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(keyword(key)?);
+        self.items.push(to_item(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Item> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeList {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Item> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Serializes maps into maps, with keys and values alternating.
+pub struct SerializeMap {
+    items: List,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Item;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(to_item(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(to_item(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Item> {
+        Ok(Item::Map(self.items, Span::default()))
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        Error(msg.to_string())
+    }
+}
+
+//
+// Private
+//
+
+fn atom(atom: Atom) -> Item {
+    Item::Atom(atom)
+}
+
+fn integer(integer: Integer) -> Item {
+    atom(Atom::Integer(integer, None, Span::default()))
+}
+
+fn list(items: List) -> Item {
+    Item::List(items, Span::default())
+}
+
+/// Returns a symbol for the name of a variant, which must be a valid symbol.
+fn symbol(name: &str) -> Result<Item> {
+    if !is_name(name) {
+        return Err(Error(format!("cannot serialize '{}' as symbol", name)));
+    }
+
+    Ok(atom(Atom::Symbol(name.into(), Span::default())))
+}
+
+/// Returns a keyword for the name of a field, which must be a valid keyword.
+fn keyword(name: &str) -> Result<Item> {
+    if !is_name(name) || name == "/" {
+        return Err(Error(format!("cannot serialize '{}' as keyword", name)));
+    }
+
+    Ok(atom(Atom::Keyword(name.into(), Span::default())))
+}
+
+/// Returns `true` if `name` would be read back as a symbol rather than as a literal.
+fn is_name(name: &str) -> bool {
+    is_qualified_symbol(name) && !["true", "false", "nil"].contains(&name)
+}
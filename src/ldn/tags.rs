@@ -0,0 +1,118 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::ast::Item;
+
+/// A handler for the values of a tag. Returns the value to keep in the tagged element, or a
+/// message describing why the value is not valid.
+pub type TagHandler = dyn Fn(&Item) -> Result<Item, String> + Send + Sync;
+
+/// Handlers for the tags of tagged elements (`#inst "1985-04-12T23:20:50.52Z"`), which validate or
+/// transform tagged values while parsing.
+///
+/// Values of tags without a handler are kept as they are.
+#[derive(Clone, Default)]
+pub struct TagRegistry {
+    handlers: HashMap<String, Arc<TagHandler>>,
+}
+
+impl TagRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler for `tag` (without the leading hash), replacing any previous one.
+    pub fn register<F>(&mut self, tag: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&Item) -> Result<Item, String> + Send + Sync + 'static,
+    {
+        self.handlers.insert(tag.into(), Arc::new(handler));
+        self
+    }
+
+    /// Returns `true` if a handler is registered for `tag`.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.handlers.contains_key(tag)
+    }
+
+    /// Runs the handler registered for `tag` on `value`. Returns a copy of `value` if there's no
+    /// handler for `tag`.
+    pub fn handle(&self, tag: &str, value: &Item) -> Result<Item, String> {
+        match self.handlers.get(tag) {
+            Some(handler) => handler(value),
+            None => Ok(value.clone()),
+        }
+    }
+}
+
+impl fmt::Debug for TagRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut tags = self.handlers.keys().collect::<Vec<_>>();
+        tags.sort();
+
+        f.debug_struct("TagRegistry").field("tags", &tags).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::ast::{Atom, StringStyle};
+    use super::super::pos::span;
+
+    #[test]
+    fn handle() {
+        let mut tags = TagRegistry::new();
+        tags.register("upper", |value| match value {
+            Item::Atom(Atom::String(s, style, span)) => Ok(Item::Atom(Atom::String(
+                s.to_uppercase(),
+                *style,
+                span.clone(),
+            ))),
+            _ => Err("expected a string".into()),
+        });
+
+        let value = Item::Atom(Atom::String(
+            "abc".into(),
+            StringStyle::Plain,
+            span(0, 0, 0, 5),
+        ));
+        let symbol = Item::Atom(Atom::Symbol("abc".into(), span(0, 0, 0, 3)));
+
+        assert!(tags.contains("upper"));
+        assert_eq!(
+            Ok(Item::Atom(Atom::String(
+                "ABC".into(),
+                StringStyle::Plain,
+                span(0, 0, 0, 5)
+            ))),
+            tags.handle("upper", &value)
+        );
+        assert_eq!(
+            Err("expected a string".to_string()),
+            tags.handle("upper", &symbol)
+        );
+
+        // Values of unknown tags are kept as they are.
+        assert!(!tags.contains("other"));
+        assert_eq!(Ok(symbol.clone()), tags.handle("other", &symbol));
+    }
+}
@@ -13,6 +13,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::char;
 use std::str;
 
 use super::error::{Error, Result};
@@ -22,6 +23,10 @@ use super::pos::{Position, Span};
 /// Wraps a position-tracking iterator to provide facilities for tokenizing the underlying text
 /// stream.
 ///
+/// Bytes are decoded as UTF-8, so that the tokenizer deals with whole characters. Malformed
+/// sequences are decoded as U+FFFD and reported as `Error::Utf8Error` by `check_ch()` and
+/// `invalid_ch()`.
+///
 /// It acts like a Peekable, in that it provides a `peek_ch()` method that can be called exactly
 /// once, but doesn't provide an implementation of the `Iterator` and `Peekable` traits/structs.
 pub struct Tokenizer<I>
@@ -29,10 +34,14 @@ where
     I: Iterator<Item = u8>,
 {
     iter: PositionIterator<I>,
+    /// A byte read past the end of a malformed UTF-8 sequence, along with its position.
+    pending: Option<(u8, Position)>,
     peeked_pos: Position,
+    recorded: Option<String>,
 
-    #[cfg_attr(feature = "cargo-clippy", allow(clippy::option_option))]
-    peeked: Option<Option<I::Item>>,
+    /// The peeked character, along with whether it was decoded from a malformed sequence.
+    #[allow(clippy::option_option)]
+    peeked: Option<Option<(char, bool)>>,
 }
 
 impl<I> Tokenizer<I>
@@ -43,78 +52,187 @@ where
     pub fn new(iter: PositionIterator<I>) -> Self {
         Self {
             iter,
+            pending: None,
             peeked_pos: Position::default(),
             peeked: None,
+            recorded: None,
         }
     }
 
-    /// Returns a token from bytes read from the underlying iterator until the stop predicate
+    /// Sets the distance between tab stops used when reporting positions, or counts tabs as a
+    /// single column when `None`.
+    pub fn set_tab_width(&mut self, tab_width: Option<usize>) {
+        self.iter.set_tab_width(tab_width);
+    }
+
+    /// Returns a token from characters read from the underlying iterator until the stop predicate
     /// returns false. Raises an error when the check predicate returns false.
     pub fn take_until<F1, F2>(&mut self, check_p: F1, stop_p: F2) -> Result<(String, Span)>
     where
-        F1: Fn(u8) -> bool,
-        F2: Fn(u8) -> bool,
+        F1: Fn(char) -> bool,
+        F2: Fn(char) -> bool,
     {
-        let mut ret: Vec<u8> = Vec::new();
+        let mut ret = String::new();
 
         let start = self.pos().clone();
 
-        while let Some(ch) = self.peek_ch() {
-            if !stop_p(*ch) {
+        while let Some(&ch) = self.peek_ch() {
+            if !stop_p(ch) {
                 break;
             }
 
-            if !check_p(*ch) {
-                return Err(Error::InvalidCharacter(*ch, self.pos().clone()));
-            }
+            self.check_ch(&check_p)?;
 
-            ret.push(*ch);
+            ret.push(ch);
 
             self.next_ch();
         }
 
         let end = self.pos().clone();
 
-        let span = Span::new(start, end);
-
-        match String::from_utf8(ret) {
-            Ok(s) => Ok((s, span)),
-            Err(_) => Err(Error::Utf8Error(span)),
-        }
+        Ok((ret, Span::new(start, end)))
     }
 
     /// Peeks a character from the iterator, without advancing the current position. Always returns
     /// the last peeked character until someone advances the underlying iterator by calling
     /// `next_ch()`.
-    pub fn peek_ch(&mut self) -> Option<&I::Item> {
+    pub fn peek_ch(&mut self) -> Option<&char> {
         if self.peeked.is_none() {
-            self.peeked_pos = self.iter.pos().clone();
-            self.peeked = Some(self.next_ch());
+            self.peeked_pos = self.byte_pos().clone();
+            self.peeked = Some(self.decode());
         }
 
         match self.peeked {
-            Some(Some(ref v)) => Some(v),
+            Some(Some((ref v, _))) => Some(v),
             Some(None) => None,
             _ => unreachable!(),
         }
     }
 
     /// Returns the next character from the underlying iterator. If `peek_ch()` was called, first
-    /// consumes the peeked byte.
-    pub fn next_ch(&mut self) -> Option<I::Item> {
-        match self.peeked.take() {
+    /// consumes the peeked character.
+    pub fn next_ch(&mut self) -> Option<char> {
+        let ch = match self.peeked.take() {
             Some(v) => v,
-            None => self.iter.next(),
+            None => self.decode(),
+        };
+
+        if let (Some(recorded), Some((ch, _))) = (self.recorded.as_mut(), ch) {
+            recorded.push(ch);
         }
+
+        ch.map(|(ch, _)| ch)
+    }
+
+    /// Returns an error if the peeked character was decoded from a malformed UTF-8 sequence or
+    /// doesn't satisfy the check predicate.
+    pub fn check_ch<F>(&mut self, check_p: F) -> Result<()>
+    where
+        F: Fn(char) -> bool,
+    {
+        match self.peek_ch().cloned() {
+            Some(ch) if check_p(ch) && !self.is_malformed() => Ok(()),
+            Some(_) => Err(self.invalid_ch()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the error reporting the peeked character as invalid: `Error::Utf8Error` if it was
+    /// decoded from a malformed UTF-8 sequence, `Error::InvalidCharacter` otherwise.
+    pub fn invalid_ch(&mut self) -> Error {
+        let ch = self
+            .peek_ch()
+            .cloned()
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+
+        if self.is_malformed() {
+            Error::Utf8Error(Span::new(self.peeked_pos.clone(), self.byte_pos().clone()))
+        } else {
+            Error::InvalidCharacter(ch, self.peeked_pos.clone())
+        }
+    }
+
+    /// Starts recording every character consumed through `next_ch()`, discarding anything
+    /// recorded so far.
+    pub fn start_recording(&mut self) {
+        self.recorded = Some(String::new());
+    }
+
+    /// Stops recording and returns the characters consumed since the last call to
+    /// `start_recording()`. Malformed UTF-8 sequences are replaced with U+FFFD.
+    pub fn stop_recording(&mut self) -> String {
+        self.recorded.take().unwrap_or_default()
     }
 
     /// Returns the current position, taking peeking into account.
     pub fn pos(&self) -> &Position {
         match self.peeked {
-            None => &self.iter.pos(),
+            None => self.byte_pos(),
             Some(_) => &self.peeked_pos,
         }
     }
+
+    //
+    // Private
+    //
+
+    /// Returns `true` if the peeked character was decoded from a malformed UTF-8 sequence.
+    fn is_malformed(&self) -> bool {
+        matches!(self.peeked, Some(Some((_, true))))
+    }
+
+    /// Returns the position of the next byte to decode.
+    fn byte_pos(&self) -> &Position {
+        match self.pending {
+            Some((_, ref pos)) => pos,
+            None => self.iter.pos(),
+        }
+    }
+
+    /// Returns the next byte, starting from the one left over by a malformed sequence, if any.
+    fn next_byte(&mut self) -> Option<u8> {
+        match self.pending.take() {
+            Some((byte, _)) => Some(byte),
+            None => self.iter.next(),
+        }
+    }
+
+    /// Decodes the next character, returning U+FFFD along with `true` if the bytes don't form a
+    /// valid UTF-8 sequence.
+    fn decode(&mut self) -> Option<(char, bool)> {
+        let malformed = Some((char::REPLACEMENT_CHARACTER, true));
+
+        let first = self.next_byte()?;
+
+        let width = match first {
+            0x00..=0x7f => return Some((char::from(first), false)),
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => return malformed,
+        };
+
+        let mut buf = [first, 0, 0, 0];
+
+        for byte in buf.iter_mut().take(width).skip(1) {
+            let pos = self.iter.pos().clone();
+
+            match self.iter.next() {
+                Some(next) if next & 0xc0 == 0x80 => *byte = next,
+                Some(next) => {
+                    // Keep the unexpected byte around, it may start the next character.
+                    self.pending = Some((next, pos));
+                    return malformed;
+                }
+                None => return malformed,
+            }
+        }
+
+        match str::from_utf8(&buf[..width]) {
+            Ok(s) => s.chars().next().map(|ch| (ch, false)),
+            Err(_) => malformed,
+        }
+    }
 }
 
 /// Creates a tokenizer from a string reference.
@@ -161,14 +279,14 @@ mod tests {
     fn take_until() {
         let mut t = Tokenizer::from("foo bar");
 
-        let s1 = t.take_until(|_| true, |ch| ch != b' ').unwrap();
+        let s1 = t.take_until(|_| true, |ch| ch != ' ').unwrap();
         assert_eq!(("foo".to_string(), span(0, 0, 0, 3)), s1);
         assert_eq!(&pos(0, 3), t.pos());
 
-        assert_eq!(Some(b' '), t.next_ch());
+        assert_eq!(Some(' '), t.next_ch());
         assert_eq!(&pos(0, 4), t.pos());
 
-        let s2 = t.take_until(|_| true, |ch| ch != b' ').unwrap();
+        let s2 = t.take_until(|_| true, |ch| ch != ' ').unwrap();
         assert_eq!(("bar".to_string(), span(0, 4, 0, 7)), s2);
         assert_eq!(&pos(0, 7), t.pos());
 
@@ -190,26 +308,40 @@ mod tests {
     fn peek_ch() {
         let mut t = Tokenizer::from("foo");
 
-        assert_eq!(Some(&b'f'), t.peek_ch());
+        assert_eq!(Some(&'f'), t.peek_ch());
         assert_eq!(&Position::default(), t.pos());
         // Repeated peeks always return the previous peek and pos
-        assert_eq!(Some(&b'f'), t.peek_ch());
+        assert_eq!(Some(&'f'), t.peek_ch());
         assert_eq!(&Position::default(), t.pos());
         // Advance
-        assert_eq!(Some(b'f'), t.next_ch());
-        assert_eq!(Some(b'o'), t.next_ch());
+        assert_eq!(Some('f'), t.next_ch());
+        assert_eq!(Some('o'), t.next_ch());
         assert_eq!(&pos(0, 2), t.pos());
         // Peek again
-        assert_eq!(Some(&b'o'), t.peek_ch());
+        assert_eq!(Some(&'o'), t.peek_ch());
         assert_eq!(&pos(0, 2), t.pos());
         // Advance again
-        assert_eq!(Some(b'o'), t.next_ch());
+        assert_eq!(Some('o'), t.next_ch());
         assert_eq!(&pos(0, 3), t.pos());
         // End
         assert_eq!(None, t.next_ch());
         assert_eq!(&pos(0, 3), t.pos());
     }
 
+    #[test]
+    fn recording() {
+        let mut t = Tokenizer::from("foo bar");
+
+        assert_eq!(Some(&'f'), t.peek_ch());
+        t.start_recording();
+        t.take_until(|_| true, |ch| ch != ' ').unwrap();
+        assert_eq!("foo", t.stop_recording());
+
+        // Nothing is recorded when not asked to.
+        t.next_ch();
+        assert_eq!("", t.stop_recording());
+    }
+
     #[test]
     fn next_ch_empty() {
         let mut t = Tokenizer::from("");
@@ -222,10 +354,68 @@ mod tests {
     fn next_ch() {
         let mut t = Tokenizer::from("foo");
 
-        assert_eq!(Some(b'f'), t.next_ch());
-        assert_eq!(Some(b'o'), t.next_ch());
-        assert_eq!(Some(b'o'), t.next_ch());
+        assert_eq!(Some('f'), t.next_ch());
+        assert_eq!(Some('o'), t.next_ch());
+        assert_eq!(Some('o'), t.next_ch());
         assert_eq!(None, t.next_ch());
         assert_eq!(&pos(0, 3), t.pos());
     }
+
+    #[test]
+    fn utf8() {
+        let mut t = Tokenizer::from("è 😀\nü");
+
+        assert_eq!(Some('è'), t.next_ch());
+        assert_eq!(&pos(0, 1), t.pos());
+        assert_eq!(Some(' '), t.next_ch());
+        assert_eq!(Some(&'😀'), t.peek_ch());
+        assert_eq!(&pos(0, 2), t.pos());
+        assert_eq!(Some('😀'), t.next_ch());
+        assert_eq!(&pos(0, 3), t.pos());
+        assert_eq!(Some('\n'), t.next_ch());
+        assert_eq!(Some('ü'), t.next_ch());
+        assert_eq!(&pos(1, 1), t.pos());
+        assert_eq!(None, t.next_ch());
+    }
+
+    #[test]
+    fn utf8_malformed() {
+        let mut t = Tokenizer::new(PositionIterator::new(
+            vec![b'a', 0xc3, b'b', 0xff, b'c'].into_iter(),
+        ));
+
+        assert_eq!(Ok(()), t.check_ch(|_| true));
+        assert_eq!(Some('a'), t.next_ch());
+
+        // Truncated sequence, the following byte is not lost.
+        assert_eq!(Some(&char::REPLACEMENT_CHARACTER), t.peek_ch());
+        assert_eq!(
+            Err(Error::Utf8Error(span(0, 1, 0, 2))),
+            t.check_ch(|_| true)
+        );
+        t.next_ch();
+        assert_eq!(Some('b'), t.next_ch());
+
+        // Invalid lead byte.
+        assert_eq!(Some(&char::REPLACEMENT_CHARACTER), t.peek_ch());
+        t.next_ch();
+        assert_eq!(Some('c'), t.next_ch());
+        assert_eq!(None, t.next_ch());
+    }
+
+    #[test]
+    fn check_ch() {
+        let mut t = Tokenizer::from("a\u{7}");
+
+        assert_eq!(Ok(()), t.check_ch(|ch| ch == 'a'));
+        assert_eq!(
+            Err(Error::InvalidCharacter('a', pos(0, 0))),
+            t.check_ch(|ch| ch != 'a')
+        );
+        t.next_ch();
+        assert_eq!(
+            Err(Error::InvalidCharacter('\u{7}', pos(0, 1))),
+            t.check_ch(|ch| !ch.is_control())
+        );
+    }
 }
@@ -0,0 +1,401 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use super::ast::{Atom, Item, Quote, StringStyle};
+use super::integer::Integer;
+use super::pos::Span;
+
+/// The value denoted by an item, without spans, comments or any detail of how it was written.
+///
+/// Values are totally ordered, first by kind in the order the variants are declared and then by
+/// their contents, so that they can be used as keys of both hashed and ordered collections.
+/// Floating-point numbers are compared with `f64::total_cmp`, so `NaN` is equal to itself while
+/// `0.0` and `-0.0` are different values.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Integer(Integer),
+    /// A ratio, as written. Like in `Atom::eq_value`, ratios are compared by their numerators and
+    /// denominators, so `1/2` and `2/4` are different values.
+    Ratio(Integer, Integer),
+    Float(f64),
+    Char(char),
+    String(String),
+    Keyword(String),
+    Symbol(String),
+    List(Vec<Value>),
+    Vector(Vec<Value>),
+    Map(BTreeMap<Value, Value>),
+    Set(BTreeSet<Value>),
+    Quoted(Quote, Box<Value>),
+    Tagged(String, Box<Value>),
+}
+
+/// The error returned when an item doesn't denote a value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TryFromItemError {
+    /// A map with a key without a value.
+    MapOddEntries(Span),
+    /// A comment or malformed item found where a value is expected.
+    NotAValue(Span),
+}
+
+impl TryFrom<&Item> for Value {
+    type Error = TryFromItemError;
+
+    /// Converts an item into the value it denotes, skipping nested comments. Fails on comments and
+    /// on malformed items. When a map repeats a key, the last entry wins.
+    fn try_from(item: &Item) -> Result<Self, Self::Error> {
+        Ok(match item {
+            Item::Atom(Atom::Bool(v, _)) => Value::Bool(*v),
+            Item::Atom(Atom::Char(v, _)) => Value::Char(*v),
+            Item::Atom(Atom::Float(v, _, _)) => Value::Float(*v),
+            Item::Atom(Atom::Integer(v, _, _)) => Value::Integer(v.clone()),
+            Item::Atom(Atom::Keyword(v, _)) => Value::Keyword(v.clone()),
+            Item::Atom(Atom::Nil(_)) => Value::Nil,
+            Item::Atom(Atom::Ratio(n, d, _)) => Value::Ratio(n.clone(), d.clone()),
+            Item::Atom(Atom::String(v, _, _)) => Value::String(v.clone()),
+            Item::Atom(Atom::Symbol(v, _)) => Value::Symbol(v.clone()),
+            Item::BlockComment(_, span)
+            | Item::Comment(_, span)
+            | Item::DatumComment(_, span)
+            | Item::Error(_, span) => return Err(TryFromItemError::NotAValue(span.clone())),
+            Item::List(items, _) => Value::List(values(items).collect::<Result<_, _>>()?),
+            Item::Map(items, span) => {
+                let items = values(items).collect::<Result<Vec<_>, _>>()?;
+
+                if !items.len().is_multiple_of(2) {
+                    return Err(TryFromItemError::MapOddEntries(span.clone()));
+                }
+
+                let mut entries = items.into_iter();
+                let mut map = BTreeMap::new();
+
+                while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                    map.insert(key, value);
+                }
+
+                Value::Map(map)
+            }
+            Item::Quoted(quote, item, _) => {
+                Value::Quoted(*quote, Box::new(Value::try_from(&**item)?))
+            }
+            Item::Set(items, _) => Value::Set(values(items).collect::<Result<_, _>>()?),
+            Item::Tagged(tag, item, _) => {
+                Value::Tagged(tag.clone(), Box::new(Value::try_from(&**item)?))
+            }
+            Item::Vector(items, _) => Value::Vector(values(items).collect::<Result<_, _>>()?),
+        })
+    }
+}
+
+impl From<&Value> for Item {
+    /// Converts a value into an item with empty spans, writing integers in decimal and strings
+    /// with escape sequences.
+    fn from(value: &Value) -> Self {
+        let span = Span::default();
+
+        match value {
+            Value::Nil => Item::Atom(Atom::Nil(span)),
+            Value::Bool(v) => Item::Atom(Atom::Bool(*v, span)),
+            Value::Integer(v) => Item::Atom(Atom::Integer(v.clone(), None, span)),
+            Value::Ratio(n, d) => Item::Atom(Atom::Ratio(n.clone(), d.clone(), span)),
+            Value::Float(v) => Item::Atom(Atom::Float(*v, None, span)),
+            Value::Char(v) => Item::Atom(Atom::Char(*v, span)),
+            Value::String(v) => Item::Atom(Atom::String(v.clone(), StringStyle::Plain, span)),
+            Value::Keyword(v) => Item::Atom(Atom::Keyword(v.clone(), span)),
+            Value::Symbol(v) => Item::Atom(Atom::Symbol(v.clone(), span)),
+            Value::List(values) => Item::List(values.iter().map(Item::from).collect(), span),
+            Value::Vector(values) => Item::Vector(values.iter().map(Item::from).collect(), span),
+            Value::Map(map) => Item::Map(
+                map.iter()
+                    .flat_map(|(key, value)| vec![Item::from(key), Item::from(value)])
+                    .collect(),
+                span,
+            ),
+            Value::Set(values) => Item::Set(values.iter().map(Item::from).collect(), span),
+            Value::Quoted(quote, value) => {
+                Item::Quoted(*quote, Box::new(Item::from(&**value)), span)
+            }
+            Value::Tagged(tag, value) => {
+                Item::Tagged(tag.clone(), Box::new(Item::from(&**value)), span)
+            }
+        }
+    }
+}
+
+impl From<Value> for Item {
+    fn from(value: Value) -> Self {
+        Item::from(&value)
+    }
+}
+
+impl fmt::Display for Value {
+    /// Writes the value as compact LDN, on a single line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Item::from(self).fmt(f)
+    }
+}
+
+impl fmt::Display for TryFromItemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryFromItemError::MapOddEntries(span) => {
+                write!(f, "{} map has a key without a value", span)
+            }
+            TryFromItemError::NotAValue(span) => write!(f, "{} expected a value", span),
+        }
+    }
+}
+
+impl error::Error for TryFromItemError {}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => Ordering::Equal,
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Ratio(a, b), Value::Ratio(c, d)) => a.cmp(c).then_with(|| b.cmp(d)),
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            (Value::Char(a), Value::Char(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b))
+            | (Value::Keyword(a), Value::Keyword(b))
+            | (Value::Symbol(a), Value::Symbol(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) | (Value::Vector(a), Value::Vector(b)) => a.cmp(b),
+            (Value::Map(a), Value::Map(b)) => a.cmp(b),
+            (Value::Set(a), Value::Set(b)) => a.cmp(b),
+            (Value::Quoted(a, a_value), Value::Quoted(b, b_value)) => {
+                a.cmp(b).then_with(|| a_value.cmp(b_value))
+            }
+            (Value::Tagged(a, a_value), Value::Tagged(b, b_value)) => {
+                a.cmp(b).then_with(|| a_value.cmp(b_value))
+            }
+            _ => kind(self).cmp(&kind(other)),
+        }
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            Value::Nil => {}
+            Value::Bool(v) => v.hash(state),
+            Value::Integer(v) => v.hash(state),
+            Value::Ratio(n, d) => {
+                n.hash(state);
+                d.hash(state);
+            }
+            Value::Float(v) => v.to_bits().hash(state),
+            Value::Char(v) => v.hash(state),
+            Value::String(v) | Value::Keyword(v) | Value::Symbol(v) => v.hash(state),
+            Value::List(values) | Value::Vector(values) => values.hash(state),
+            Value::Map(map) => map.hash(state),
+            Value::Set(values) => values.hash(state),
+            Value::Quoted(quote, value) => {
+                quote.hash(state);
+                value.hash(state);
+            }
+            Value::Tagged(tag, value) => {
+                tag.hash(state);
+                value.hash(state);
+            }
+        }
+    }
+}
+
+//
+// Private
+//
+
+/// Returns the position of the value's variant in the declaration of `Value`.
+fn kind(value: &Value) -> usize {
+    match value {
+        Value::Nil => 0,
+        Value::Bool(_) => 1,
+        Value::Integer(_) => 2,
+        Value::Ratio(_, _) => 3,
+        Value::Float(_) => 4,
+        Value::Char(_) => 5,
+        Value::String(_) => 6,
+        Value::Keyword(_) => 7,
+        Value::Symbol(_) => 8,
+        Value::List(_) => 9,
+        Value::Vector(_) => 10,
+        Value::Map(_) => 11,
+        Value::Set(_) => 12,
+        Value::Quoted(_, _) => 13,
+        Value::Tagged(_, _) => 14,
+    }
+}
+
+/// Converts the items that are not comments.
+fn values<'a>(items: &'a [Item]) -> impl Iterator<Item = Result<Value, TryFromItemError>> + 'a {
+    items
+        .iter()
+        .filter(|item| !item.is_comment())
+        .map(Value::try_from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use super::super::options::Options;
+    use super::super::parser::Parser;
+    use super::super::pos::span;
+
+    fn parse(input: &str) -> Vec<Value> {
+        Parser::from(input)
+            .with_options(Options::extended())
+            .parse()
+            .unwrap()
+            .iter()
+            .filter(|item| !item.is_comment())
+            .map(|item| Value::try_from(item).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn from_item() {
+        assert_eq!(
+            vec![
+                Value::Map(
+                    vec![
+                        (
+                            Value::Keyword("a".into()),
+                            Value::Integer(Integer::from(255))
+                        ),
+                        (
+                            Value::Keyword("b".into()),
+                            Value::Set(
+                                vec![Value::String("x".into()), Value::Char('y')]
+                                    .into_iter()
+                                    .collect()
+                            )
+                        ),
+                    ]
+                    .into_iter()
+                    .collect()
+                ),
+                Value::Quoted(
+                    Quote::Quote,
+                    Box::new(Value::Tagged(
+                        "inst".into(),
+                        Box::new(Value::Vector(vec![Value::Nil, Value::Float(1.5)]))
+                    ))
+                ),
+            ],
+            parse("{:b #{\"x\" \\y} :a 0xff} ; map\n'#inst [nil #_ 1 1.5]")
+        );
+
+        assert_eq!(
+            Err(TryFromItemError::NotAValue(span(0, 0, 0, 3))),
+            Value::try_from(&Item::Comment(" a".into(), span(0, 0, 0, 3)))
+        );
+
+        assert_eq!(
+            Err(TryFromItemError::NotAValue(span(0, 4, 0, 6))),
+            Value::try_from(&Item::Vector(
+                vec![
+                    Item::Atom(Atom::Nil(span(0, 1, 0, 4))),
+                    Item::Error("0x".into(), span(0, 4, 0, 6))
+                ],
+                span(0, 0, 0, 7)
+            ))
+        );
+
+        assert_eq!(
+            Err(TryFromItemError::MapOddEntries(span(0, 0, 0, 5))),
+            Value::try_from(&Item::Map(
+                vec![Item::Atom(Atom::Nil(span(0, 1, 0, 4)))],
+                span(0, 0, 0, 5)
+            ))
+        );
+    }
+
+    #[test]
+    fn to_item() {
+        let input = "(a :b \"c\" \\d 1/2 -2.5 [true] {1 2} #{} `,x #uuid \"u\")";
+
+        for value in parse(input) {
+            let item = Item::from(&value);
+
+            assert_eq!(value, Value::try_from(&item).unwrap());
+        }
+    }
+
+    #[test]
+    fn eq() {
+        assert_eq!(
+            parse("(1 {:a 1 :b 2})"),
+            parse("( 0x1 ; one\n  {:b 0b10 :a 1})")
+        );
+        assert_eq!(parse("\"a\""), parse("#\"a\"#"));
+        assert_ne!(parse("1"), parse("1.0"));
+        assert_ne!(parse("1/2"), parse("2/4"));
+        assert_ne!(parse("(1)"), parse("[1]"));
+        assert_ne!(parse("0.0"), parse("-0.0"));
+        assert_eq!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+    }
+
+    #[test]
+    fn hash() {
+        let set = parse("{1 2} {0x1 0b10} 1.5 1.5 :a :a a")
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        assert_eq!(4, set.len());
+    }
+
+    #[test]
+    fn ord() {
+        let mut values = parse("[] () a :a \"a\" \\a 1.5 1/2 -1 true nil #{} {} 'x #t x 2");
+
+        values.sort();
+
+        assert_eq!(
+            parse("nil true -1 2 1/2 1.5 \\a \"a\" :a a () [] {} #{} 'x #t x"),
+            values
+        );
+    }
+}
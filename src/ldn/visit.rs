@@ -0,0 +1,185 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Read-only traversal of items.
+
+use super::ast::{Atom, Item};
+
+/// The items containing the visited one, from the outermost to its direct parent.
+pub type Path<'a> = Vec<&'a Item>;
+
+/// Visits items depth-first, in the order they appear in the document.
+///
+/// Every item is entered before its children and exited after them. The children of lists,
+/// vectors, maps and sets are visited through `visit_list`, while quoted items, tagged values and
+/// the items of datum comments are visited directly. All hooks receive the path of the items
+/// containing the current one.
+pub trait Visitor<'a> {
+    /// Called before visiting the children of an item. Returning `false` skips them.
+    fn enter(&mut self, _item: &'a Item, _path: &Path<'a>) -> bool {
+        true
+    }
+
+    /// Called after visiting the children of an item, even when they were skipped.
+    fn exit(&mut self, _item: &'a Item, _path: &Path<'a>) {}
+
+    /// Called for the atom of an atom item, between entering and exiting it.
+    fn visit_atom(&mut self, _atom: &'a Atom, _path: &Path<'a>) {}
+
+    /// Called for the items of a list, vector, map or set, and for top-level items. The path ends
+    /// with the containing item, if any.
+    fn visit_list(&mut self, items: &'a [Item], path: &mut Path<'a>) {
+        walk_list(self, items, path);
+    }
+}
+
+/// Visits a sequence of top-level items.
+pub fn walk<'a, V>(visitor: &mut V, items: &'a [Item])
+where
+    V: Visitor<'a> + ?Sized,
+{
+    visitor.visit_list(items, &mut vec![]);
+}
+
+/// Visits each of the given items.
+pub fn walk_list<'a, V>(visitor: &mut V, items: &'a [Item], path: &mut Path<'a>)
+where
+    V: Visitor<'a> + ?Sized,
+{
+    for item in items {
+        walk_item(visitor, item, path);
+    }
+}
+
+/// Visits an item and, unless the visitor skips them, its children.
+pub fn walk_item<'a, V>(visitor: &mut V, item: &'a Item, path: &mut Path<'a>)
+where
+    V: Visitor<'a> + ?Sized,
+{
+    if visitor.enter(item, path) {
+        match item {
+            Item::Atom(atom) => visitor.visit_atom(atom, path),
+            Item::BlockComment(_, _) | Item::Comment(_, _) | Item::Error(_, _) => {}
+            Item::DatumComment(value, _)
+            | Item::Quoted(_, value, _)
+            | Item::Tagged(_, value, _) => {
+                path.push(item);
+                walk_item(visitor, value, path);
+                path.pop();
+            }
+            Item::List(items, _)
+            | Item::Map(items, _)
+            | Item::Set(items, _)
+            | Item::Vector(items, _) => {
+                path.push(item);
+                visitor.visit_list(items, path);
+                path.pop();
+            }
+        }
+    }
+
+    visitor.exit(item, path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::options::Options;
+    use super::super::parser::Parser;
+
+    fn parse(input: &str) -> Vec<Item> {
+        Parser::from(input)
+            .with_options(Options::extended())
+            .parse()
+            .unwrap()
+    }
+
+    /// Records the events of a traversal, skipping datum comments.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl<'a> Visitor<'a> for Recorder {
+        fn enter(&mut self, item: &'a Item, path: &Path<'a>) -> bool {
+            self.events.push(format!("enter {} {}", path.len(), item));
+            !matches!(item, Item::DatumComment(_, _))
+        }
+
+        fn exit(&mut self, item: &'a Item, path: &Path<'a>) {
+            self.events.push(format!("exit {} {}", path.len(), item));
+        }
+
+        fn visit_atom(&mut self, atom: &'a Atom, path: &Path<'a>) {
+            let parent = path.last().map(|item| item.to_string());
+
+            self.events
+                .push(format!("atom {:?} {:?}", atom.name(), parent));
+        }
+    }
+
+    #[test]
+    fn visit() {
+        let items = parse("(a #_(b) 'c) :d");
+        let mut recorder = Recorder::default();
+
+        walk(&mut recorder, &items);
+
+        assert_eq!(
+            vec![
+                "enter 0 (a #_(b) 'c)",
+                "enter 1 a",
+                "atom Some(\"a\") Some(\"(a #_(b) 'c)\")",
+                "exit 1 a",
+                "enter 1 #_(b)",
+                "exit 1 #_(b)",
+                "enter 1 'c",
+                "enter 2 c",
+                "atom Some(\"c\") Some(\"'c\")",
+                "exit 2 c",
+                "exit 1 'c",
+                "exit 0 (a #_(b) 'c)",
+                "enter 0 :d",
+                "atom Some(\"d\") None",
+                "exit 0 :d",
+            ],
+            recorder.events
+        );
+    }
+
+    #[test]
+    fn visit_list() {
+        /// Counts the entries of maps, without visiting them.
+        #[derive(Default)]
+        struct Entries(usize);
+
+        impl<'a> Visitor<'a> for Entries {
+            fn visit_list(&mut self, items: &'a [Item], path: &mut Path<'a>) {
+                match path.last() {
+                    Some(Item::Map(_, _)) => self.0 += items.len() / 2,
+                    _ => walk_list(self, items, path),
+                }
+            }
+        }
+
+        let items = parse("{:a {:b 1}} [{:c 2 :d 3}]");
+        let mut entries = Entries::default();
+
+        walk(&mut entries, &items);
+
+        assert_eq!(3, entries.0);
+    }
+}
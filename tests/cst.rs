@@ -0,0 +1,40 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path;
+
+use pretty_assertions::assert_eq;
+
+use lithium::ldn::{cst, Parser};
+
+#[test]
+fn cst_round_trip_by_example() {
+    let mut input_files = ["testdata/fmt/input", "testdata/fmt/expected"]
+        .iter()
+        .flat_map(|dir| fs::read_dir(dir).unwrap())
+        .map(|x| x.unwrap().path())
+        .collect::<Vec<path::PathBuf>>();
+
+    input_files.sort_unstable();
+
+    for input_file in input_files {
+        let text = fs::read_to_string(&input_file).unwrap();
+        let root = cst::parse(&text);
+
+        assert_eq!(text, root.to_string());
+        assert_eq!(Parser::from(text.as_ref()).parse(), root.to_items());
+    }
+}
@@ -18,7 +18,7 @@ use std::path;
 
 use pretty_assertions::assert_eq;
 
-use lithium::ldn::{fmt, Parser};
+use lithium::ldn::{fmt, Atom, Integer, Item, Options, Parser, Span, StringStyle};
 
 #[test]
 fn fmt_by_example() {
@@ -45,3 +45,150 @@ fn fmt_by_example() {
         assert_eq!(expected, actual);
     }
 }
+
+#[test]
+fn fmt_integers_changed() {
+    // Literals that don't denote the value anymore are replaced by its decimal notation.
+    let items = vec![
+        Item::Atom(Atom::Integer(
+            Integer::from(255),
+            Some("0xF_F".into()),
+            Span::default(),
+        )),
+        Item::Atom(Atom::Integer(
+            Integer::from(2),
+            Some("0x1".into()),
+            Span::default(),
+        )),
+    ];
+
+    assert_eq!("0xF_F\n\n2\n", fmt(&items));
+}
+
+#[test]
+fn fmt_floats_and_ratios() {
+    let items = Parser::from("(1.5 -0.25 2E10 1e5 +1.50e-3 3/4 -6/8)")
+        .with_options(Options::extended())
+        .parse()
+        .unwrap();
+
+    assert_eq!("(1.5 -0.25 2E10 1e5 +1.50e-3 3/4 -6/8)\n", fmt(&items));
+
+    // Literals that don't denote the value anymore are replaced by the shortest notation.
+    let items = vec![Item::Atom(Atom::Float(
+        100000.0,
+        Some("1e4".into()),
+        Span::default(),
+    ))];
+
+    assert_eq!("100000.0\n", fmt(&items));
+}
+
+#[test]
+fn fmt_collections() {
+    let items = Parser::from("[1 {:a  #{:b}\n:c [] }]\n(x)")
+        .with_options(Options::extended())
+        .parse()
+        .unwrap();
+
+    assert_eq!("[1 {:a #{:b}\n        :c []}]\n\n(x)\n", fmt(&items));
+}
+
+#[test]
+fn fmt_tagged() {
+    let items = Parser::from("(#inst  \"1985-04-12\" #a #b\n[1])")
+        .with_options(Options::extended())
+        .parse()
+        .unwrap();
+
+    assert_eq!("(#inst \"1985-04-12\" #a #b [1])\n", fmt(&items));
+}
+
+#[test]
+fn fmt_quotes() {
+    let items = Parser::from("'a `(b ,c\n,@ d)")
+        .with_options(Options::extended())
+        .parse()
+        .unwrap();
+
+    assert_eq!("'a\n\n`(b ,c\n    ,@d)\n", fmt(&items));
+}
+
+#[test]
+fn fmt_block_and_datum_comments() {
+    let items = Parser::from("#| a\n  b |#\n(a #_  (b\n c) #|d|#)")
+        .parse()
+        .unwrap();
+
+    assert_eq!("#| a\n  b |#\n(a #_(b\n        c) #|d|#)\n", fmt(&items));
+
+    // Line breaks are counted from the end of multi-line items.
+    let items = Parser::from("#| a\n |#\nx").parse().unwrap();
+
+    assert_eq!("#| a\n |#\nx\n", fmt(&items));
+}
+
+#[test]
+fn fmt_trailing_comment() {
+    let texts = [
+        "(1 ; c\n)\n",
+        "[1 ; c\n]\n",
+        "{:a 1 ; c\n}\n",
+        "#{1 ; c\n}\n",
+        "(a [b\n        ; c\n    ])\n",
+    ];
+
+    for text in &texts {
+        let items = Parser::from(*text)
+            .with_options(Options::extended())
+            .parse()
+            .unwrap();
+
+        assert_eq!(*text, fmt(&items));
+    }
+}
+
+#[test]
+fn fmt_line_endings() {
+    let items = Parser::from("#| a\r\n b |#\r\n(a\r\n\tb)\r")
+        .parse()
+        .unwrap();
+
+    assert_eq!("#| a\n b |#\n(a\n    b)\n", fmt(&items));
+}
+
+#[test]
+fn fmt_literals() {
+    let items = Parser::from("(true nil \\a \\space \\u{7} \\u{e8} \\))")
+        .parse()
+        .unwrap();
+
+    assert_eq!("(true nil \\a \\space \\u{7} \\è \\))\n", fmt(&items));
+}
+
+#[test]
+fn fmt_strings_round_trip() {
+    let values = [
+        "",
+        "\n",
+        "  a\n b",
+        "a  \n\t\n",
+        "a\"",
+        "\"\"\"\"\"",
+        "\"#\"##\\",
+        "a\rb\u{7}",
+    ];
+
+    for style in &[StringStyle::Plain, StringStyle::Raw(1), StringStyle::Block] {
+        for value in &values {
+            let string = Atom::String(value.to_string(), *style, Span::default());
+            let items = vec![Item::List(
+                vec![Item::Atom(string.clone())],
+                Span::default(),
+            )];
+            let parsed = Parser::from(fmt(&items).as_ref()).parse().unwrap();
+
+            assert!(items[0].eq_value(&parsed[0]), "{:?}", string);
+        }
+    }
+}
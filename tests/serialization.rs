@@ -0,0 +1,255 @@
+// Lithium
+// Copyright (C) 2018 Lorenzo Villani
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use pretty_assertions::assert_eq;
+use serde::{Deserialize, Serialize};
+
+use lithium::ldn::{de, from_str, ser, to_string, Span};
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Config {
+    name: String,
+    port: u16,
+    verbose: bool,
+    ratio: f64,
+    separator: char,
+    timeout: Option<u32>,
+    hosts: Vec<String>,
+    limits: BTreeMap<String, u64>,
+    shapes: Vec<Shape>,
+    id: Id,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Point(i32, i32),
+    Rect { width: u32, height: u32 },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Id(u64);
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+enum Untagged {
+    Number(i64),
+    Text(String),
+    Point { x: i32, y: i32 },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type")]
+enum Tagged {
+    Circle { radius: f64 },
+    Square { side: u32 },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Flattened {
+    name: String,
+    #[serde(flatten)]
+    extra: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Wrapper {
+    value: Untagged,
+}
+
+fn config() -> Config {
+    Config {
+        name: "a \"b\"".into(),
+        port: 8080,
+        verbose: true,
+        ratio: 0.5,
+        separator: ',',
+        timeout: None,
+        hosts: vec!["x".into(), "y".into()],
+        limits: vec![("max".to_string(), u64::MAX)].into_iter().collect(),
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Point(-1, 2),
+            Shape::Rect {
+                width: 3,
+                height: 4,
+            },
+        ],
+        id: Id(7),
+    }
+}
+
+#[test]
+fn serialize() {
+    assert_eq!(
+        concat!(
+            r#"(:name "a \"b\"" :port 8080 :verbose true :ratio 0.5 :separator \, :timeout nil "#,
+            r#":hosts ("x" "y") :limits {"max" 18446744073709551615} :shapes (Empty (Circle 1.5) "#,
+            r#"(Point -1 2) (Rect :width 3 :height 4)) :id 7)"#,
+            "\n"
+        ),
+        to_string(&config()).unwrap()
+    );
+
+    assert_eq!(
+        Err(ser::Error("cannot serialize NaN as LDN".into())),
+        to_string(&f64::NAN)
+    );
+
+    #[derive(Serialize)]
+    enum Literal {
+        #[serde(rename = "nil")]
+        Nil,
+    }
+
+    assert_eq!(
+        Err(ser::Error("cannot serialize 'nil' as symbol".into())),
+        to_string(&Literal::Nil)
+    );
+}
+
+#[test]
+fn deserialize() {
+    assert_eq!(config(), from_str(&to_string(&config()).unwrap()).unwrap());
+
+    // Control characters are written as Unicode escapes.
+    let bell = vec!['\u{7}'];
+    assert_eq!(
+        bell,
+        from_str::<Vec<char>>(&to_string(&bell).unwrap()).unwrap()
+    );
+
+    // Lists of keywords are sequences when a sequence is expected.
+    assert_eq!(
+        Ok(vec!["web".to_string(), "api".to_string()]),
+        from_str::<Vec<String>>("(:web :api)")
+    );
+    assert_eq!(
+        Ok(("a".to_string(), "b".to_string())),
+        from_str::<(String, String)>("[:a :b]")
+    );
+
+    // Comments are skipped, structs can be read from maps and missing options are `None`.
+    let text = r#"
+        ; A point.
+        {:y #| ignored |# 2 :x 1}
+    "#;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Point {
+        x: i8,
+        y: i8,
+        z: Option<i8>,
+    }
+
+    assert_eq!(
+        Point {
+            x: 1,
+            y: 2,
+            z: None
+        },
+        from_str(text).unwrap()
+    );
+}
+
+#[test]
+fn round_trip_buffered() {
+    let untagged = vec![
+        Untagged::Number(-1),
+        Untagged::Text("a".into()),
+        Untagged::Point { x: 1, y: 2 },
+    ];
+    let text = to_string(&untagged).unwrap();
+    assert_eq!("(-1 \"a\" (:x 1 :y 2))\n", text);
+    assert_eq!(Ok(untagged), from_str(&text));
+
+    let tagged = vec![Tagged::Circle { radius: 1.5 }, Tagged::Square { side: 2 }];
+    let text = to_string(&tagged).unwrap();
+    assert_eq!(
+        "((:type \"Circle\" :radius 1.5) (:type \"Square\" :side 2))\n",
+        text
+    );
+    assert_eq!(Ok(tagged), from_str(&text));
+
+    let flattened = Flattened {
+        name: "a".into(),
+        extra: vec![("b".to_string(), 1)].into_iter().collect(),
+    };
+    let text = to_string(&flattened).unwrap();
+    assert_eq!(Ok(flattened), from_str(&text));
+}
+
+#[test]
+fn deserialize_errors() {
+    assert_eq!(
+        Err(de::Error::Message(
+            "invalid type: string \"80\", expected u16".into(),
+            Span::from_parts(0, 17, 0, 21)
+        )),
+        from_str::<Config>(r#"(:name "a" :port "80")"#)
+    );
+
+    assert_eq!(
+        Err(de::Error::Message(
+            "missing field `port`".into(),
+            Span::from_parts(0, 0, 0, 11)
+        )),
+        from_str::<Config>(r#"(:name "a")"#)
+    );
+
+    assert_eq!(
+        Err(de::Error::Message(
+            "unknown variant `Square`, expected one of `Empty`, `Circle`, `Point`, `Rect`".into(),
+            Span::from_parts(1, 1, 1, 7)
+        )),
+        from_str::<Shape>("\n(Square 1)")
+    );
+
+    assert_eq!(
+        Err(de::Error::Message(
+            "invalid length 3, expected fewer elements".into(),
+            Span::from_parts(0, 0, 0, 13)
+        )),
+        from_str::<Shape>("(Point 1 2 3)")
+    );
+
+    assert_eq!(
+        Err(de::Error::Message(
+            "invalid value: integer `300`, expected u8".into(),
+            Span::from_parts(0, 1, 0, 4)
+        )),
+        from_str::<Vec<u8>>("(300)")
+    );
+
+    assert_eq!(
+        Err(de::Error::Message(
+            "expected a single value".into(),
+            Span::from_parts(0, 2, 0, 3)
+        )),
+        from_str::<u8>("1 2")
+    );
+
+    assert_eq!(
+        Err(de::Error::Message(
+            "data did not match any variant of untagged enum Untagged".into(),
+            Span::from_parts(0, 8, 0, 14)
+        )),
+        from_str::<Wrapper>("(:value (:z 1))")
+    );
+}